@@ -2,9 +2,10 @@ use nom::{
     IResult,
     bytes::complete::tag,
     multi::{separated_list1, many1},
-    character::complete::{u64, space1},
+    character::complete::{u64, space1, alpha1, multispace0},
 };
 use itertools::Itertools;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::ops::Range;
 
 fn main() {
@@ -20,22 +21,24 @@ fn main() {
 }
 
 fn part1(input: &str) -> u64 {
-    let input = Input::parse(input);
+    let almanac = Almanac::parse(input).expect("valid almanac");
+    let path = almanac.path("seed", "location").expect("seeds lead to locations");
 
-    input.seeds
+    almanac.seeds
         .iter()
-        .map(|&seed| input.seed_to_location(seed))
+        .map(|&seed| Almanac::convert(&path, seed))
         .min()
         .expect("at least one seed")
 }
 
 fn part2(input: &str) -> u64 {
-    let input = Input::parse(input);
-    let seed_ranges = input.seeds.iter().tuples::<(&u64, &u64)>();
+    let almanac = Almanac::parse(input).expect("valid almanac");
+    let path = almanac.path("seed", "location").expect("seeds lead to locations");
+    let seed_ranges = almanac.seeds.iter().tuples::<(&u64, &u64)>();
 
     #[allow(clippy::map_flatten)]
     seed_ranges
-        .map(|(&start, &length)| input.seeds_to_locations(start..(start+length)))
+        .map(|(&start, &length)| Almanac::convert_range(&path, start..(start+length)))
         .flatten()
         .map(|range| range.start)
         .min()
@@ -59,90 +62,176 @@ fn rangemapper(input: &str) -> IResult<&str, RangeMapper> {
     Ok((input, RangeMapper {ranges}))
 }
 
-fn input_parser(input: &str) -> IResult<&str, Input> {
+fn category_map(input: &str) -> IResult<&str, CategoryMap<'_>> {
+    let (input, _) = tag("\n")(input)?;
+    let (input, from) = alpha1(input)?;
+    let (input, _) = tag("-to-")(input)?;
+    let (input, to) = alpha1(input)?;
+    let (input, _) = tag(" map:\n")(input)?;
+    let (input, mapper) = rangemapper(input)?;
+    Ok((input, CategoryMap {from, to, mapper}))
+}
+
+fn input_parser(input: &str) -> IResult<&str, (Vec<u64>, Vec<CategoryMap<'_>>)> {
     let (input, _) = tag("seeds: ")(input)?;
     let (input, seeds) = separated_list1(space1, u64)(input)?;
-    let (input, _) = tag("\n\nseed-to-soil map:\n")(input)?;
-    let (input, seed_to_soil) = rangemapper(input)?;
-    let (input, _) = tag("\nsoil-to-fertilizer map:\n")(input)?;
-    let (input, soil_to_fertilizer) = rangemapper(input)?;
-    let (input, _) = tag("\nfertilizer-to-water map:\n")(input)?;
-    let (input, fertilizer_to_water) = rangemapper(input)?;
-    let (input, _) = tag("\nwater-to-light map:\n")(input)?;
-    let (input, water_to_light) = rangemapper(input)?;
-    let (input, _) = tag("\nlight-to-temperature map:\n")(input)?;
-    let (input, light_to_temp) = rangemapper(input)?;
-    let (input, _) = tag("\ntemperature-to-humidity map:\n")(input)?;
-    let (input, temp_to_humid) = rangemapper(input)?;
-    let (input, _) = tag("\nhumidity-to-location map:\n")(input)?;
-    let (input, humid_to_location) = rangemapper(input)?;
-    
-    Ok((input,
-        Input {
-            seeds,
-            seed_to_soil,
-            soil_to_fertilizer,
-            fertilizer_to_water,
-            water_to_light,
-            light_to_temp,
-            temp_to_humid,
-            humid_to_location
+    let (input, _) = tag("\n")(input)?;
+    let (input, maps) = many1(category_map)(input)?;
+    let (input, _) = multispace0(input)?;
+    Ok((input, (seeds, maps)))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum AlmanacError {
+    Parse(String),
+    DuplicateMap { from: String, to: String },
+    Cycle(Vec<String>),
+    UnknownCategory(String),
+    NoPath { from: String, to: String },
+}
+
+impl std::fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlmanacError::Parse(rest) => write!(f, "unable to parse almanac near {rest:?}"),
+            AlmanacError::DuplicateMap { from, to } => write!(f, "more than one {from}-to-{to} map"),
+            AlmanacError::Cycle(categories) => write!(f, "cyclic maps: {}", categories.join(" -> ")),
+            AlmanacError::UnknownCategory(category) => write!(f, "no map mentions category {category:?}"),
+            AlmanacError::NoPath { from, to } => write!(f, "no chain of maps leads from {from} to {to}"),
         }
-    ))
+    }
 }
 
-struct Input {
+impl std::error::Error for AlmanacError {}
+
+struct CategoryMap<'a> {
+    from: &'a str,
+    to: &'a str,
+    mapper: RangeMapper,
+}
+
+// The maps form a directed graph of categories.  A conversion between two
+// categories follows the chain of maps connecting them.
+struct Almanac<'a> {
     seeds: Vec<u64>,
-    seed_to_soil: RangeMapper,
-    soil_to_fertilizer: RangeMapper,
-    fertilizer_to_water: RangeMapper,
-    water_to_light: RangeMapper,
-    light_to_temp: RangeMapper,
-    temp_to_humid: RangeMapper,
-    humid_to_location: RangeMapper
+    maps: Vec<CategoryMap<'a>>,
 }
 
-impl Input {
-    fn seed_to_location(&self, seed: u64) -> u64 {
-        let soil = self.seed_to_soil.map(seed);
-        let fertilizer = self.soil_to_fertilizer.map(soil);
-        let water = self.fertilizer_to_water.map(fertilizer);
-        let light = self.water_to_light.map(water);
-        let temp = self.light_to_temp.map(light);
-        let humid = self.temp_to_humid.map(temp);
-        self.humid_to_location.map(humid)
+impl<'a> Almanac<'a> {
+    fn parse(input: &'a str) -> Result<Self, AlmanacError> {
+        let (rest, (seeds, maps)) = input_parser(input)
+            .map_err(|e| AlmanacError::Parse(match e {
+                nom::Err::Error(e) | nom::Err::Failure(e) => e.input.lines().next().unwrap_or("").to_string(),
+                nom::Err::Incomplete(_) => String::new(),
+            }))?;
+        if !rest.is_empty() {
+            return Err(AlmanacError::Parse(rest.lines().next().unwrap_or("").to_string()));
+        }
+
+        for (i, m) in maps.iter().enumerate() {
+            if maps[..i].iter().any(|other| other.from == m.from && other.to == m.to) {
+                return Err(AlmanacError::DuplicateMap { from: m.from.to_string(), to: m.to.to_string() });
+            }
+        }
+
+        let almanac = Almanac { seeds, maps };
+        if let Some(cycle) = almanac.find_cycle() {
+            return Err(AlmanacError::Cycle(cycle.into_iter().map(String::from).collect()));
+        }
+        Ok(almanac)
     }
 
-    #[allow(clippy::map_flatten)]
-    fn seeds_to_locations(&self, seeds: Range<u64>) -> Vec<Range<u64>> {
-        // println!("seeds: {seeds:?}");
-        let length = seeds.end - seeds.start;
-        let soils = self.seed_to_soil.map_range(seeds);
-        // println!("soils: {soils:?}");
-        let fertilizers = soils.into_iter().map(|range| self.soil_to_fertilizer.map_range(range)).flatten();
-        // println!("fertilizers: {fertilizers:?}");
-        let waters = fertilizers.map(|range| self.fertilizer_to_water.map_range(range)).flatten();
-        // println!("waters: {waters:?}");
-        let lights = waters.map(|range| self.water_to_light.map_range(range)).flatten();
-        // println!("lights: {lights:?}");
-        let temps = lights.map(|range| self.light_to_temp.map_range(range)).flatten();
-        // println!("temps: {temps:?}");
-        let humids = temps.map(|range| self.temp_to_humid.map_range(range)).flatten();
-        // println!("humids: {humids:?}");
-        let locations = humids.map(|range| self.humid_to_location.map_range(range)).flatten().collect_vec();
-        // println!("locations: {locations:?}");
-
-        assert_eq!(length, locations.iter().map(|r| r.end-r.start).sum());
-        locations
+    fn categories(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.maps.iter().flat_map(|m| [m.from, m.to]).unique()
+    }
+
+    fn successors(&self, category: &str) -> impl Iterator<Item = (usize, &'a str)> + '_ {
+        let category = category.to_string();
+        self.maps
+            .iter()
+            .enumerate()
+            .filter(move |(_, m)| m.from == category)
+            .map(|(i, m)| (i, m.to))
     }
 
-    fn parse(input: &str) -> Self {
-        input_parser(input)
-            .expect("unable to parse")
-            .1
+    // Depth-first search, returning the categories along the first cycle found
+    // (with the first category repeated at the end).
+    fn find_cycle(&self) -> Option<Vec<&'a str>> {
+        fn visit<'a>(
+            almanac: &Almanac<'a>,
+            category: &'a str,
+            stack: &mut Vec<&'a str>,
+            done: &mut HashSet<&'a str>
+        ) -> Option<Vec<&'a str>> {
+            if let Some(pos) = stack.iter().position(|&c| c == category) {
+                let mut cycle = stack[pos..].to_vec();
+                cycle.push(category);
+                return Some(cycle);
+            }
+            if done.contains(category) {
+                return None;
+            }
+            stack.push(category);
+            for (_, next) in almanac.successors(category) {
+                if let Some(cycle) = visit(almanac, next, stack, done) {
+                    return Some(cycle);
+                }
+            }
+            stack.pop();
+            done.insert(category);
+            None
+        }
+
+        let mut done = HashSet::new();
+        self.categories().find_map(|category| visit(self, category, &mut vec![], &mut done))
+    }
+
+    // Breadth-first search for the shortest chain of maps from one category
+    // to another.
+    fn path(&self, from: &str, to: &str) -> Result<Vec<&RangeMapper>, AlmanacError> {
+        for category in [from, to] {
+            if !self.categories().any(|c| c == category) {
+                return Err(AlmanacError::UnknownCategory(category.to_string()));
+            }
+        }
+
+        let mut came_from: HashMap<&str, Option<usize>> = HashMap::from([(from, None)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(category) = queue.pop_front() {
+            if category == to {
+                let mut path = vec![];
+                let mut current = to;
+                while let Some(index) = came_from[current] {
+                    path.push(index);
+                    current = self.maps[index].from;
+                }
+                return Ok(path.into_iter().rev().map(|i| &self.maps[i].mapper).collect());
+            }
+            for (index, next) in self.successors(category) {
+                if let std::collections::hash_map::Entry::Vacant(e) = came_from.entry(next) {
+                    e.insert(Some(index));
+                    queue.push_back(next);
+                }
+            }
+        }
+        Err(AlmanacError::NoPath { from: from.to_string(), to: to.to_string() })
+    }
+
+    fn convert(path: &[&RangeMapper], value: u64) -> u64 {
+        path.iter().fold(value, |value, mapper| mapper.map(value))
+    }
+
+    fn convert_range(path: &[&RangeMapper], values: Range<u64>) -> Vec<Range<u64>> {
+        let length = values.end - values.start;
+        let result = path.iter().fold(vec![values], |ranges, mapper| {
+            ranges.into_iter().flat_map(|range| mapper.map_range(range)).collect()
+        });
+        assert_eq!(length, result.iter().map(|r| r.end-r.start).sum());
+        result
     }
 }
 
+#[derive(Debug)]
 struct RangeMap {
     dest_start: u64,
     src_start: u64,
@@ -159,6 +248,7 @@ impl RangeMap {
     }
 }
 
+#[derive(Debug)]
 struct RangeMapper {
     ranges: Vec<RangeMap>
 }
@@ -255,4 +345,69 @@ fn test_part1_example1() {
 #[test]
 fn test_part2_example1() {
     assert_eq!(part2(EXAMPLE1_STR), 46);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_soil_to_humidity() {
+    let almanac = Almanac::parse(EXAMPLE1_STR).unwrap();
+    let path = almanac.path("soil", "humidity").unwrap();
+    assert_eq!(path.len(), 5);
+    assert_eq!(Almanac::convert(&path, 81), 78);
+}
+
+#[test]
+fn test_reordered_and_extra_stages() {
+    let input = "\
+seeds: 1 5
+
+water-to-light map:
+100 0 10
+
+seed-to-soil map:
+10 0 10
+
+soil-to-water map:
+20 10 10
+
+soil-to-mineral map:
+0 0 1
+";
+    let almanac = Almanac::parse(input).unwrap();
+    let path = almanac.path("seed", "light").unwrap();
+    assert_eq!(Almanac::convert(&path, 1), 21);
+    assert_eq!(Almanac::convert(&path, 5), 25);
+    assert_eq!(Almanac::convert_range(&path, 8..12), vec![28..30, 20..22]);
+}
+
+#[test]
+fn test_missing_link() {
+    let almanac = Almanac::parse(EXAMPLE1_STR).unwrap();
+    assert_eq!(
+        almanac.path("location", "seed").unwrap_err(),
+        AlmanacError::NoPath { from: "location".to_string(), to: "seed".to_string() }
+    );
+    assert_eq!(
+        almanac.path("seed", "gold").unwrap_err(),
+        AlmanacError::UnknownCategory("gold".to_string())
+    );
+}
+
+#[test]
+fn test_cyclic_links() {
+    let input = "\
+seeds: 1
+
+seed-to-soil map:
+0 0 1
+
+soil-to-water map:
+0 0 1
+
+water-to-soil map:
+0 0 1
+";
+    assert_eq!(
+        Almanac::parse(input).err(),
+        Some(AlmanacError::Cycle(vec!["soil".to_string(), "water".to_string(), "soil".to_string()]))
+    );
+}
@@ -1,81 +1,90 @@
+use num::{BigUint, One, Zero};
+
 fn main() {
     let input = include_str!("../input.txt");
 
     let result1 = part1(input);
     println!("Part 1: {result1}");
-    assert_eq!(result1, 633080);
+    assert_eq!(result1, BigUint::from(633080u32));
 
     let result2 = part2(input);
     println!("Part 2: {result2}");
-    assert_eq!(result2, 20048741);
+    assert_eq!(result2, BigUint::from(20048741u32));
+}
+
+fn part1(input: &str) -> BigUint {
+    parse_races(input)
+        .iter()
+        .map(|(time, distance)| ways_to_win(time, distance))
+        .product()
 }
 
-fn part1(input: &str) -> u32 {
+fn part2(input: &str) -> BigUint {
+    let (time, distance) = parse_kerned_race(input);
+    ways_to_win(&time, &distance)
+}
+
+// Each column of numbers is a separate race.
+fn parse_races(input: &str) -> Vec<(BigUint, BigUint)> {
     let mut lines = input.lines();
-    let times: Vec<u32> = lines
-        .next()
-        .expect("at least one line")
-        .split_whitespace()
-        .skip(1)
-        .map(|s| s.parse().expect("valid number"))
-        .collect();
-    let distances: Vec<u32> = lines
-        .next()
-        .expect("at least two lines")
-        .split_whitespace()
-        .skip(1)
-        .map(|s| s.parse().expect("valid number"))
-        .collect();
-
-    let mut result = 1;
-    for (time, distance) in times.into_iter().zip(distances.into_iter()) {
-        // Let's just brute force.  Try holding the button for 1 ms up to time-1,
-        // and compute the resulting distance, comparing to the best distance.
-        result *= (1..time)
-            .map(|charge| charge * (time - charge))
-            .filter(|dist| dist > &distance)
-            .count() as u32;
-    }
-    result
+    let mut numbers = || -> Vec<BigUint> {
+        lines
+            .next()
+            .expect("one line")
+            .split_whitespace()
+            .skip(1)
+            .map(|s| s.parse().expect("valid number"))
+            .collect()
+    };
+    let times = numbers();
+    let distances = numbers();
+    assert_eq!(times.len(), distances.len());
+    times.into_iter().zip(distances).collect()
 }
 
-fn part2(input: &str) -> usize {
+// Bad kerning: the digits on each line are one big number.
+fn parse_kerned_race(input: &str) -> (BigUint, BigUint) {
     let mut lines = input.lines();
-    let time: usize = lines
-        .next()
-        .expect("one line")
-        .split_once(':')
-        .expect("colon")
-        .1
-        .replace(' ', "")
-        .parse()
-        .expect("valid number");
-    let distance: usize = lines
-        .next()
-        .expect("one line")
-        .split_once(':')
-        .expect("colon")
-        .1
-        .replace(' ', "")
-        .parse()
-        .expect("valid number");
-
-    // Find a win with the smallest possible charge time
-    let mut lower = 0;          // Always a loser
-    let mut upper = time / 2;   // Always a winner
-    while lower+1 < upper {
-        let mid = (lower + upper) / 2;
-        if mid * (time - mid) > distance {
-            upper = mid;
-        } else {
-            lower = mid;
-        }
+    let mut number = || -> BigUint {
+        lines
+            .next()
+            .expect("one line")
+            .split_once(':')
+            .expect("colon")
+            .1
+            .replace(' ', "")
+            .parse()
+            .expect("valid number")
+    };
+    let time = number();
+    let distance = number();
+    (time, distance)
+}
+
+// Holding the button for `c` ms wins if c * (time - c) > distance, i.e.
+// c² - time*c + distance < 0.  The winning charge times lie strictly between
+// the roots (time ± √(time² - 4*distance)) / 2, so we can count them using
+// an exact integer square root instead of trying every charge time.
+fn ways_to_win(time: &BigUint, distance: &BigUint) -> BigUint {
+    let four_d = distance * 4u32;
+    let t_squared = time * time;
+    if t_squared <= four_d {
+        // No real roots, or a single root that merely ties the record
+        return BigUint::zero();
     }
-    let shortest = upper;
+    let discriminant = t_squared - four_d;
+    let s = discriminant.sqrt();
 
-    // The charge time and move time are symmetric, so the longest charge
-    // time is (time - shortest).
-    (time - shortest) - shortest + 1
+    if &s * &s == discriminant {
+        // Both roots are integers (s and time have the same parity), and they
+        // only tie the record, so exclude them.
+        s - BigUint::one()
+    } else {
+        // The smallest winning charge time is ⌈(time - s) / 2⌉; the largest
+        // is symmetric, time - shortest.
+        let shortest = (time - &s + BigUint::one()) / 2u32;
+        time + BigUint::one() - shortest * 2u32
+    }
 }
 
 #[cfg(test)]
@@ -86,10 +95,50 @@ Distance:  9  40  200
 
 #[test]
 fn test_part1_example() {
-    assert_eq!(part1(EXAMPLE_INPUT), 288);
+    assert_eq!(part1(EXAMPLE_INPUT), BigUint::from(288u32));
 }
 
 #[test]
 fn test_part2_example() {
-    assert_eq!(part2(EXAMPLE_INPUT), 71503);
+    assert_eq!(part2(EXAMPLE_INPUT), BigUint::from(71503u32));
+}
+
+#[cfg(test)]
+fn brute_force(time: u64, distance: u64) -> u64 {
+    (0..=time).filter(|charge| charge * (time - charge) > distance).count() as u64
+}
+
+#[test]
+fn test_integer_roots() {
+    // Roots at exactly 10 and 20, which only tie the record
+    assert_eq!(ways_to_win(&30u32.into(), &200u32.into()), BigUint::from(9u32));
+    // A single double root at 2
+    assert_eq!(ways_to_win(&4u32.into(), &4u32.into()), BigUint::zero());
+    // Roots at 0 and time
+    assert_eq!(ways_to_win(&7u32.into(), &0u32.into()), BigUint::from(6u32));
+    // No real roots
+    assert_eq!(ways_to_win(&4u32.into(), &5u32.into()), BigUint::zero());
+}
+
+#[test]
+fn test_matches_brute_force() {
+    for time in 0..60u64 {
+        for distance in 0..=(time * time / 4 + 1) {
+            assert_eq!(
+                ways_to_win(&time.into(), &distance.into()),
+                BigUint::from(brute_force(time, distance)),
+                "time={time} distance={distance}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_huge_race() {
+    // Roots at 10^30 and 10^31 - 10^30, far beyond u64
+    let time: BigUint = "10000000000000000000000000000000".parse().unwrap();
+    let root: BigUint = "1000000000000000000000000000000".parse().unwrap();
+    let distance = &root * (&time - &root);
+    assert_eq!(ways_to_win(&time, &distance), &time - &root * 2u32 - BigUint::one());
+    assert_eq!(ways_to_win(&time, &(distance - BigUint::one())), &time - &root * 2u32 + BigUint::one());
 }
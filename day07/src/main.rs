@@ -10,88 +10,181 @@ fn main() {
     let result2 = part2(input);
     println!("Part 2: {result2}");
     assert_eq!(result2, 253907829);
+
+    // With `--explain`, show how every hand was ranked, so a wrong answer
+    // can be diffed against a reference.
+    if std::env::args().any(|arg| arg == "--explain") {
+        println!();
+        println!("Part 1 hands:");
+        print!("{}", explain(input, &Rules::camel_cards()));
+        println!();
+        println!("Part 2 hands:");
+        print!("{}", explain(input, &Rules::camel_cards_with_jokers()));
+    }
 }
 
 fn part1(input: &str) -> u32 {
-    let mut hands = input.lines().map(|s| Hand::from_str(s, false)).collect_vec();
-    hands.sort();
-    hands.iter().enumerate().map(|(i, hand)| (i as u32 +1) * hand.bid).sum()
+    total_winnings(input, &Rules::camel_cards())
 }
 
 fn part2(input: &str) -> u32 {
-    let mut hands = input.lines().map(|s| Hand::from_str(s, true)).collect_vec();
+    total_winnings(input, &Rules::camel_cards_with_jokers())
+}
+
+fn total_winnings(input: &str, rules: &Rules) -> u32 {
+    ranked_hands(input, rules).iter().enumerate().map(|(i, hand)| (i as u32 +1) * hand.bid).sum()
+}
+
+fn ranked_hands(input: &str, rules: &Rules) -> Vec<Hand> {
+    let mut hands = input.lines().map(|s| Hand::parse(s, rules)).collect_vec();
     hands.sort();
-    hands.iter().enumerate().map(|(i, hand)| (i as u32 +1) * hand.bid).sum()
+    hands
+}
+
+// One line per hand, weakest first, with the rank, bid and winnings:
+//   rank  cards → kind (wild→substitute)  bid  winnings
+fn explain(input: &str, rules: &Rules) -> String {
+    let hands = ranked_hands(input, rules);
+    let mut result = String::new();
+    let mut total = 0;
+    for (i, hand) in hands.iter().enumerate() {
+        let rank = i as u32 + 1;
+        let winnings = rank * hand.bid;
+        total += winnings;
+        let substitution = match hand.wild_as {
+            Some(rank) => {
+                let wilds: String = hand.text.chars().filter(|&c| rules.is_wild(c)).unique().collect();
+                format!(" ({wilds}→{rank})")
+            }
+            None => String::new(),
+        };
+        result.push_str(&format!(
+            "{rank:>5}  {} → {}{substitution}  bid {} × {rank} = {winnings}\n",
+            hand.text, hand.kind, hand.bid
+        ));
+    }
+    result.push_str(&format!("Total winnings: {total}\n"));
+    result
+}
+
+// The rules of a card game: how many cards make a hand, the ranks from
+// weakest to strongest, and which ranks (if any) are wild.  A wild card
+// joins whichever group makes the hand strongest, but when breaking ties
+// it is worth `wildcard_value` (0 is weaker than every rank in `ranks`).
+#[derive(Debug, Clone)]
+struct Rules {
+    hand_size: usize,
+    ranks: String,
+    wildcards: String,
+    wildcard_value: u32,
 }
 
+impl Rules {
+    fn camel_cards() -> Self {
+        Rules {
+            hand_size: 5,
+            ranks: "23456789TJQKA".to_string(),
+            wildcards: String::new(),
+            wildcard_value: 0,
+        }
+    }
+
+    fn camel_cards_with_jokers() -> Self {
+        Rules {
+            wildcards: "J".to_string(),
+            ..Self::camel_cards()
+        }
+    }
+
+    fn is_wild(&self, card: char) -> bool {
+        self.wildcards.contains(card)
+    }
+
+    fn card_value(&self, card: char) -> u32 {
+        if self.is_wild(card) {
+            self.wildcard_value
+        } else {
+            let index = self.ranks.find(card).expect("unknown card");
+            index as u32 + 1
+        }
+    }
+}
+
+// A kind of hand is the sizes of its groups of matching cards, largest first.
+// Comparing those lexicographically ranks hands of any size: for five cards,
+// [5] > [4, 1] > [3, 2] > [3, 1, 1] > [2, 2, 1] > [2, 1, 1, 1] > [1, 1, 1, 1, 1].
 #[derive(Debug, PartialEq, PartialOrd, Eq, Ord)]
-enum HandKind {
-    HighCard,
-    OnePair,
-    TwoPair,
-    ThreeOfAKind,
-    FullHouse,
-    FourOfAKind,
-    FiveOfAKind,
+struct HandKind {
+    groups: Vec<usize>,
+}
+
+impl HandKind {
+    fn from_cards(cards: &[char]) -> Self {
+        let mut groups = cards.iter().counts().into_values().collect_vec();
+        groups.sort_unstable_by(|a, b| b.cmp(a));
+        HandKind { groups }
+    }
+}
+
+impl std::fmt::Display for HandKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.groups.as_slice() {
+            [5] => write!(f, "five of a kind"),
+            [4, 1] => write!(f, "four of a kind"),
+            [3, 2] => write!(f, "full house"),
+            [3, 1, 1] => write!(f, "three of a kind"),
+            [2, 2, 1] => write!(f, "two pair"),
+            [2, 1, 1, 1] => write!(f, "one pair"),
+            groups if groups.iter().all(|&n| n == 1) => write!(f, "high card"),
+            groups => write!(f, "groups of {}", groups.iter().join("+")),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 struct Hand {
+    text: String,
     cards: Vec<u32>,
     bid: u32,
     kind: HandKind,
+    wild_as: Option<char>,
 }
 
 impl Hand {
-    fn from_str(s: &str, jokers: bool) -> Self {
+    fn parse(s: &str, rules: &Rules) -> Self {
         let (cards_str, bid_str) = s.split_once(' ').expect("a space");
-        let cards: Vec<u32> = cards_str
-            .chars()
-            .map(|c| match c {
-                d if c.is_ascii_digit() => d.to_digit(10).unwrap(),
-                'T' => 10,
-                'J' => if jokers { 1 } else { 11 },
-                'Q' => 12,
-                'K' => 13,
-                'A' => 14,
-                _ => panic!("unknown card"),
-            })
-            .collect();
-        assert_eq!(cards.len(), 5);
+        let chars = cards_str.chars().collect_vec();
+        assert_eq!(chars.len(), rules.hand_size, "wrong number of cards in {cards_str:?}");
+        let cards = chars.iter().map(|&c| rules.card_value(c)).collect();
         let bid = bid_str.parse().expect("valid bid");
 
-        // Figure out the kind of hand.
-        let mut sorted_cards = cards.clone();
-        sorted_cards.sort_unstable();
-        let mut groups: Vec<_> = sorted_cards.into_iter().group_by(|card| *card).into_iter().map(|(_key, group)| group.count()).collect();
-        groups.sort_unstable();
-        if jokers {
-            // Find out how many jokers there were
-            let num_jokers = cards_str.chars().filter(|&card| card == 'J').count();
-            if num_jokers > 0 && num_jokers < 5 {
-                // Remove the jokers group, and add them to the largest group
-                let jokers_index = groups.binary_search(&num_jokers).unwrap();
-                groups.remove(jokers_index);
-                *(groups.last_mut().unwrap()) += num_jokers;
-            }
-        }
-        let kind = if groups == vec![5] {
-            HandKind::FiveOfAKind
-        } else if groups == vec![1, 4] {
-            HandKind::FourOfAKind
-        } else if groups == vec![2, 3] {
-            HandKind::FullHouse
-        } else if groups == vec![1, 1, 3] {
-            HandKind::ThreeOfAKind
-        } else if groups == vec![1, 2, 2] {
-            HandKind::TwoPair
-        } else if groups == vec![1, 1, 1, 2] {
-            HandKind::OnePair
-        } else {
-            HandKind::HighCard
-        };
+        // Wild cards are best spent making the largest group even larger,
+        // so they all stand in for the same rank.
+        let wild_as = Self::best_substitute(&chars, rules);
+        let substituted = chars
+            .iter()
+            .map(|&c| if rules.is_wild(c) { wild_as.unwrap() } else { c })
+            .collect_vec();
+        let kind = HandKind::from_cards(&substituted);
 
-        Hand { cards, bid, kind }
+        Hand { text: cards_str.to_string(), cards, bid, kind, wild_as }
+    }
+
+    // The rank that the wild cards should become, or None if there are no
+    // wild cards.  Among equally large groups, pick the strongest rank.
+    // If every card is wild, they become the strongest rank.
+    fn best_substitute(cards: &[char], rules: &Rules) -> Option<char> {
+        if !cards.iter().any(|&c| rules.is_wild(c)) {
+            return None;
+        }
+        let best = cards
+            .iter()
+            .filter(|&&c| !rules.is_wild(c))
+            .counts()
+            .into_iter()
+            .max_by_key(|&(&c, count)| (count, rules.card_value(c)))
+            .map(|(&c, _)| c);
+        best.or_else(|| rules.ranks.chars().rev().find(|&c| !rules.is_wild(c)))
     }
 }
 
@@ -129,3 +222,48 @@ fn test_part1_example() {
 fn test_part2_example() {
     assert_eq!(part2(EXAMPLE_INPUT), 5905);
 }
+
+#[test]
+fn test_hand_kinds() {
+    let rules = Rules::camel_cards_with_jokers();
+    let kind = |cards: &str| Hand::parse(&format!("{cards} 0"), &rules).kind.groups;
+    assert_eq!(kind("32T3K"), vec![2, 1, 1, 1]);
+    assert_eq!(kind("KK677"), vec![2, 2, 1]);
+    assert_eq!(kind("T55J5"), vec![4, 1]);
+    assert_eq!(kind("KTJJT"), vec![4, 1]);
+    assert_eq!(kind("JJJJJ"), vec![5]);
+}
+
+#[test]
+fn test_six_cards_two_wild_ranks() {
+    let rules = Rules {
+        hand_size: 6,
+        wildcards: "J2".to_string(),
+        ..Rules::camel_cards()
+    };
+    let input = "\
+AAKKQQ 1
+AJ2KQT 10
+22JJ22 100
+AAAKKQ 1000
+";
+    // Ranks, weakest first:
+    //   AAKKQQ [2, 2, 2]
+    //   AJ2KQT [3, 1, 1, 1]
+    //   AAAKKQ [3, 2, 1]
+    //   22JJ22 [6]
+    assert_eq!(total_winnings(input, &rules), 1 + 10 * 2 + 1000 * 3 + 100 * 4);
+}
+
+#[test]
+fn test_explain() {
+    let explanation = explain(EXAMPLE_INPUT, &Rules::camel_cards_with_jokers());
+    assert_eq!(explanation.lines().collect_vec(), vec![
+        "    1  32T3K → one pair  bid 765 × 1 = 765",
+        "    2  KK677 → two pair  bid 28 × 2 = 56",
+        "    3  T55J5 → four of a kind (J→5)  bid 684 × 3 = 2052",
+        "    4  QQQJA → four of a kind (J→Q)  bid 483 × 4 = 1932",
+        "    5  KTJJT → four of a kind (J→T)  bid 220 × 5 = 1100",
+        "Total winnings: 5905",
+    ]);
+}
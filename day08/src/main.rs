@@ -1,4 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use itertools::Itertools;
+use num::integer::{ExtendedGcd, Integer};
 
 fn main() {
     let input = include_str!("../input.txt");
@@ -7,33 +9,38 @@ fn main() {
     println!("Part 1: {result1}");
     assert_eq!(result1, 13301);
 
-    let result2 = part2(input);
+    let result2 = part2(input).expect("the ghosts are never all on Z nodes at once");
     println!("Part 2: {result2}");
     assert_eq!(result2, 7309459565207);
+
+    // `--dot FILE` writes the network as Graphviz, highlighting the path
+    // from AAA to ZZZ.  `--dot-ghosts FILE` highlights each ghost's path
+    // instead.  Either one also lists the strongly connected components.
+    let args: Vec<String> = std::env::args().collect();
+    for (flag, ghosts) in [("--dot", false), ("--dot-ghosts", true)] {
+        if let Some(pos) = args.iter().position(|arg| arg == flag) {
+            let path = args.get(pos + 1).expect("a file name after the flag");
+            let network = Network::parse(input);
+            let starts: Vec<&str> = if ghosts {
+                network.nodes.keys().filter(|key| key.ends_with('A')).copied().sorted().collect()
+            } else {
+                vec!["AAA"]
+            };
+            std::fs::write(path, network.to_dot(&starts)).expect("able to write DOT file");
+            println!("Wrote {path}");
+            for (i, component) in network.cyclic_components().iter().enumerate() {
+                println!("SCC {i} ({} nodes): {}", component.len(), component.join(" "));
+            }
+        }
+    }
 }
 
 fn part1(input: &str) -> u32 {
-    let mut lines = input.lines();
-    let mut directions = lines.next().unwrap().chars().cycle();
-    assert_eq!(lines.next().unwrap(), "");
-    let nodes: HashMap<&str, (&str, &str)> = lines
-        .map(|line| {
-            let (key, values) = line.split_once(" = ").unwrap();
-            let values = values.strip_prefix('(').unwrap().strip_suffix(')').unwrap();
-            let (left, right) = values.split_once(", ").unwrap();
-            (key, (left, right))
-        })
-        .collect();
-
+    let network = Network::parse(input);
     let mut steps = 0;
     let mut current = "AAA";
     while current != "ZZZ" {
-        let children = nodes.get(current).unwrap();
-        current = match directions.next().unwrap() {
-            'L' => children.0,
-            'R' => children.1,
-            _ => panic!("invalid direction"),
-        };
+        current = network.step(current, steps as usize);
         steps += 1;
     }
     steps
@@ -43,68 +50,294 @@ fn part1(input: &str) -> u32 {
 // The answer for part 2 is sufficiently large that it is impractical to
 // try to simulate the set of steps from each starting state in parallel.
 //
-// Let's call the answer N.  Since the total number of states is far
-// smaller than N, each starting state will produce a cycle.  The answer,
-// N, will be congruent to the number of steps until the cycle starts,
-// modulo the number of steps in the cycle.  (Here, a cycle refers to
-// a state that is a valid ending state; i.e., it ends with "Z".)
+// Let's call the answer N.  A state is a node plus a position in the
+// L/R instructions.  Since the total number of states is far smaller
+// than N, each starting node eventually repeats a state and enters a
+// cycle.  Every time the cycle visits a node ending in "Z", we get a
+// congruence: N ≡ step (mod cycle length).  A single cycle may visit
+// several such nodes, so each ghost contributes a set of residues.
+// Combining one residue from each ghost with the generalised Chinese
+// Remainder Theorem (the cycle lengths need not be coprime) gives the
+// candidates for N.  Before every ghost is inside its cycle, we just
+// check each step directly.
 //
-fn part2(input: &str) -> usize {
-    let mut lines = input.lines();
-    let mut directions = lines.next().unwrap().chars().cycle();
-    assert_eq!(lines.next().unwrap(), "");
-    let nodes: HashMap<&str, (&str, &str)> = lines
-        .map(|line| {
-            let (key, values) = line.split_once(" = ").unwrap();
-            let values = values.strip_prefix('(').unwrap().strip_suffix(')').unwrap();
-            let (left, right) = values.split_once(", ").unwrap();
-            (key, (left, right))
-        })
-        .collect();
-
-    let starting_states: Vec<&str> = nodes
+fn part2(input: &str) -> Option<usize> {
+    let network = Network::parse(input);
+    let cycles: Vec<GhostCycle> = network
+        .nodes
         .keys()
         .filter(|key| key.ends_with('A'))
-        .cloned()
+        .map(|initial| GhostCycle::find(&network, initial))
         .collect();
 
-    // Find the cycles for each of the starting states
-    let cycles: Vec<_> = starting_states
+    // Look for an answer before all of the ghosts are in their cycles
+    let all_cycling = cycles.iter().map(|cycle| cycle.start).max()?;
+    if let Some(steps) = (0..all_cycling).find(|&steps| cycles.iter().all(|cycle| cycle.is_end(steps))) {
+        return Some(steps);
+    }
+
+    // Combine the congruences, keeping every possible residue
+    let mut solutions: Vec<(usize, usize)> = vec![(0, 1)];
+    for cycle in cycles.iter() {
+        let mut combined: Vec<(usize, usize)> = solutions
+            .iter()
+            .flat_map(|&(residue, modulus)| {
+                cycle.hits
+                    .iter()
+                    .filter(|&&hit| hit >= cycle.start)
+                    .filter_map(move |&hit| crt(residue, modulus, hit % cycle.length, cycle.length))
+            })
+            .collect();
+        combined.sort_unstable();
+        combined.dedup();
+        solutions = combined;
+    }
+
+    // The smallest solution at or after the point where all ghosts are cycling
+    solutions
         .into_iter()
-        .map(|initial| {
-            let mut current = initial;
-            let mut steps = 0;
-            let mut seen: HashMap<&str, usize> = HashMap::new();
-            while !seen.contains_key(current) {
-                if current.ends_with('Z') {
-                    seen.insert(current, steps);
-                }
-                let children = nodes.get(current).unwrap();
-                current = match directions.next().unwrap() {
-                    'L' => children.0,
-                    'R' => children.1,
-                    _ => panic!("invalid direction"),
-                };
-                steps += 1;
+        .map(|(residue, modulus)| {
+            if residue >= all_cycling {
+                residue
+            } else {
+                residue + (all_cycling - residue).div_ceil(modulus) * modulus
             }
-            let cycle_start = *seen.get(current).unwrap();
-            let cycle_length = steps - cycle_start;
-            (cycle_start, cycle_length)
         })
-        .collect();
+        .min()
+}
+
+// Generalised Chinese Remainder Theorem: combine x ≡ a1 (mod m1) and
+// x ≡ a2 (mod m2) into x ≡ a (mod lcm(m1, m2)), or None if the two
+// congruences are incompatible.
+fn crt(a1: usize, m1: usize, a2: usize, m2: usize) -> Option<(usize, usize)> {
+    let (a1, m1, a2, m2) = (a1 as i128, m1 as i128, a2 as i128, m2 as i128);
+    let ExtendedGcd { gcd, x, .. } = m1.extended_gcd(&m2);
+    if (a2 - a1) % gcd != 0 {
+        return None;
+    }
+    let lcm = m1 / gcd * m2;
+    // m1 * x ≡ gcd (mod m2), so a1 + m1 * x * (a2 - a1) / gcd ≡ a2 (mod m2)
+    let k = (x * ((a2 - a1) / gcd)).rem_euclid(m2 / gcd);
+    let a = (a1 + m1 * k).rem_euclid(lcm);
+    Some((a as usize, lcm as usize))
+}
+
+struct Network<'a> {
+    directions: Vec<char>,
+    nodes: HashMap<&'a str, (&'a str, &'a str)>,
+}
+
+impl<'a> Network<'a> {
+    fn parse(input: &'a str) -> Self {
+        let mut lines = input.lines();
+        let directions = lines.next().unwrap().chars().collect();
+        assert_eq!(lines.next().unwrap(), "");
+        let nodes = lines
+            .map(|line| {
+                let (key, values) = line.split_once(" = ").unwrap();
+                let values = values.strip_prefix('(').unwrap().strip_suffix(')').unwrap();
+                let (left, right) = values.split_once(", ").unwrap();
+                (key, (left, right))
+            })
+            .collect();
+        Network { directions, nodes }
+    }
+
+    // Follow the direction for the given step number
+    fn step(&self, node: &str, steps: usize) -> &'a str {
+        let children = self.nodes.get(node).unwrap();
+        match self.directions[steps % self.directions.len()] {
+            'L' => children.0,
+            'R' => children.1,
+            _ => panic!("invalid direction"),
+        }
+    }
+}
+
+// The path of one ghost.  After `start` steps, it repeats every `length`
+// steps.  `hits` are the step numbers where it is on a "Z" node, both
+// before the cycle starts and within the first trip around the cycle.
+struct GhostCycle {
+    start: usize,
+    length: usize,
+    hits: Vec<usize>,
+}
+
+impl GhostCycle {
+    fn find(network: &Network, initial: &str) -> Self {
+        let num_directions = network.directions.len();
+        let mut current = initial;
+        let mut steps = 0;
+        let mut hits = Vec::new();
+        let mut seen: HashMap<(&str, usize), usize> = HashMap::new();
+        while let std::collections::hash_map::Entry::Vacant(e) = seen.entry((current, steps % num_directions)) {
+            e.insert(steps);
+            if current.ends_with('Z') {
+                hits.push(steps);
+            }
+            current = network.step(current, steps);
+            steps += 1;
+        }
+        let start = seen[&(current, steps % num_directions)];
+        let length = steps - start;
+        GhostCycle { start, length, hits }
+    }
+
+    fn is_end(&self, steps: usize) -> bool {
+        let steps = if steps < self.start {
+            steps
+        } else {
+            self.start + (steps - self.start) % self.length
+        };
+        self.hits.binary_search(&steps).is_ok()
+    }
+}
+
+// Colours for the walked paths, one per starting node
+const PATH_COLORS: [&str; 6] = ["blue", "red", "darkgreen", "darkorange", "purple", "brown"];
+
+impl<'a> Network<'a> {
+    // The edges (node, direction) taken when walking from `initial`.  The walk
+    // from AAA stops at ZZZ; any other walk stops once its state (node and
+    // position in the instructions) repeats.
+    fn walked_edges(&self, initial: &'a str) -> HashSet<(&'a str, char)> {
+        let num_directions = self.directions.len();
+        let mut edges = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut current = initial;
+        let mut steps = 0;
+        while seen.insert((current, steps % num_directions)) {
+            if initial == "AAA" && current == "ZZZ" {
+                break;
+            }
+            edges.insert((current, self.directions[steps % num_directions]));
+            current = self.step(current, steps);
+            steps += 1;
+        }
+        edges
+    }
+
+    fn to_dot(&self, starts: &[&'a str]) -> String {
+        let walks: Vec<HashSet<(&str, char)>> = starts.iter().map(|start| self.walked_edges(start)).collect();
+        let mut result = String::from("digraph network {\n");
+        result.push_str("    node [shape=circle];\n");
+
+        for (i, component) in self.cyclic_components().iter().enumerate() {
+            result.push_str(&format!("    subgraph cluster_{i} {{\n"));
+            result.push_str(&format!("        label=\"SCC {i}\";\n"));
+            for node in component {
+                result.push_str(&format!("        \"{node}\";\n"));
+            }
+            result.push_str("    }\n");
+        }
 
-    // While debugging, I noticed that cycle_start == cycle_length for
-    // each cycle.  That simplifies calculating the answer.  I'll leave
-    // a more general solution for another time.
-    assert!(cycles
-        .iter()
-        .all(|(cycle_start, cycle_length)| cycle_start == cycle_length));
+        for &node in self.nodes.keys().sorted() {
+            if node.ends_with('A') {
+                result.push_str(&format!("    \"{node}\" [shape=doublecircle, style=filled, fillcolor=palegreen];\n"));
+            } else if node.ends_with('Z') {
+                result.push_str(&format!("    \"{node}\" [shape=doublecircle, style=filled, fillcolor=lightcoral];\n"));
+            }
+        }
+
+        for &node in self.nodes.keys().sorted() {
+            let (left, right) = self.nodes[node];
+            let edges = if left == right {
+                vec![(left, "LR")]
+            } else {
+                vec![(left, "L"), (right, "R")]
+            };
+            for (child, label) in edges {
+                let walked = walks
+                    .iter()
+                    .position(|walk| label.chars().any(|direction| walk.contains(&(node, direction))));
+                let style = match walked {
+                    Some(index) => format!(", color={}, penwidth=3", PATH_COLORS[index % PATH_COLORS.len()]),
+                    None => String::new(),
+                };
+                result.push_str(&format!("    \"{node}\" -> \"{child}\" [label=\"{label}\"{style}];\n"));
+            }
+        }
+
+        result.push_str("}\n");
+        result
+    }
+
+    // Tarjan's algorithm for strongly connected components, ignoring the
+    // L/R instructions.  Each component is sorted, and the components are
+    // sorted by their first node.
+    fn strongly_connected_components(&self) -> Vec<Vec<&'a str>> {
+        struct Tarjan<'a, 'n> {
+            network: &'n Network<'a>,
+            index: HashMap<&'a str, usize>,
+            low_link: HashMap<&'a str, usize>,
+            stack: Vec<&'a str>,
+            on_stack: HashSet<&'a str>,
+            components: Vec<Vec<&'a str>>,
+        }
+
+        impl<'a, 'n> Tarjan<'a, 'n> {
+            fn visit(&mut self, node: &'a str) {
+                let index = self.index.len();
+                self.index.insert(node, index);
+                self.low_link.insert(node, index);
+                self.stack.push(node);
+                self.on_stack.insert(node);
+
+                let (left, right) = self.network.nodes[node];
+                for child in [left, right] {
+                    if !self.index.contains_key(child) {
+                        self.visit(child);
+                        let low = self.low_link[node].min(self.low_link[child]);
+                        self.low_link.insert(node, low);
+                    } else if self.on_stack.contains(child) {
+                        let low = self.low_link[node].min(self.index[child]);
+                        self.low_link.insert(node, low);
+                    }
+                }
+
+                if self.low_link[node] == self.index[node] {
+                    let mut component = Vec::new();
+                    loop {
+                        let member = self.stack.pop().unwrap();
+                        self.on_stack.remove(member);
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    component.sort_unstable();
+                    self.components.push(component);
+                }
+            }
+        }
 
-    cycles
-        .iter()
-        .map(|(_start, length)| *length)
-        .reduce(num::integer::lcm)
-        .unwrap()
+        let mut tarjan = Tarjan {
+            network: self,
+            index: HashMap::new(),
+            low_link: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            components: Vec::new(),
+        };
+        for &node in self.nodes.keys().sorted() {
+            if !tarjan.index.contains_key(node) {
+                tarjan.visit(node);
+            }
+        }
+        tarjan.components.sort_unstable();
+        tarjan.components
+    }
+
+    // The components that contain a cycle, which is where every walk ends up
+    fn cyclic_components(&self) -> Vec<Vec<&'a str>> {
+        self.strongly_connected_components()
+            .into_iter()
+            .filter(|component| {
+                let (left, right) = self.nodes[component[0]];
+                component.len() > 1 || left == component[0] || right == component[0]
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -155,5 +388,94 @@ fn test_part1_ex2() {
 
 #[test]
 fn test_part2_ex3() {
-    assert_eq!(part2(EXAMPLE_INPUT_3), 6);
+    assert_eq!(part2(EXAMPLE_INPUT_3), Some(6));
+}
+
+#[test]
+fn test_part2_offset_cycles() {
+    // Z at steps 3, 5, 7, ...  and 1, 4, 7, ...
+    let input = "\
+L
+
+11A = (11B, 11B)
+11B = (11C, 11C)
+11C = (11Z, 11Z)
+11Z = (11C, 11C)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+";
+    assert_eq!(part2(input), Some(7));
+}
+
+#[test]
+fn test_part2_multiple_z_per_cycle() {
+    // Z at steps 1, 2, 5, 6, 9, 10, ...  and 3, 6, 9, ...
+    let input = "\
+L
+
+11A = (11Z, 11Z)
+11Z = (12Z, 12Z)
+12Z = (11B, 11B)
+11B = (11C, 11C)
+11C = (11Z, 11Z)
+22A = (22B, 22B)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+";
+    assert_eq!(part2(input), Some(6));
+}
+
+#[test]
+fn test_part2_unsolvable() {
+    // Z at steps 1, 2, 5, 6, 9, 10, ...  and 3, 7, 11, ...
+    let input = "\
+L
+
+11A = (11Z, 11Z)
+11Z = (12Z, 12Z)
+12Z = (11B, 11B)
+11B = (11C, 11C)
+11C = (11Z, 11Z)
+22A = (22B, 22B)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22A, 22A)
+";
+    assert_eq!(part2(input), None);
+}
+
+#[test]
+fn test_crt() {
+    assert_eq!(crt(2, 3, 3, 5), Some((8, 15)));
+    assert_eq!(crt(1, 4, 3, 6), Some((9, 12)));
+    assert_eq!(crt(1, 4, 2, 6), None);
+}
+
+#[test]
+fn test_strongly_connected_components() {
+    let network = Network::parse(EXAMPLE_INPUT_3);
+    assert_eq!(network.strongly_connected_components(), vec![
+        vec!["11A"], vec!["11B", "11Z"], vec!["22A"], vec!["22B", "22C", "22Z"], vec!["XXX"]
+    ]);
+    assert_eq!(network.cyclic_components(), vec![
+        vec!["11B", "11Z"], vec!["22B", "22C", "22Z"], vec!["XXX"]
+    ]);
+}
+
+#[test]
+fn test_to_dot() {
+    let network = Network::parse(EXAMPLE_INPUT_2);
+    let dot = network.to_dot(&["AAA"]);
+    let lines: Vec<&str> = dot.lines().collect();
+    assert_eq!(lines[0], "digraph network {");
+    assert!(lines.contains(&"    \"AAA\" [shape=doublecircle, style=filled, fillcolor=palegreen];"));
+    assert!(lines.contains(&"    \"ZZZ\" [shape=doublecircle, style=filled, fillcolor=lightcoral];"));
+    assert!(lines.contains(&"    \"AAA\" -> \"BBB\" [label=\"LR\", color=blue, penwidth=3];"));
+    assert!(lines.contains(&"    \"BBB\" -> \"AAA\" [label=\"L\", color=blue, penwidth=3];"));
+    assert!(lines.contains(&"    \"BBB\" -> \"ZZZ\" [label=\"R\", color=blue, penwidth=3];"));
+    assert!(lines.contains(&"    \"ZZZ\" -> \"ZZZ\" [label=\"LR\"];"));
+    assert_eq!(lines.last(), Some(&"}"));
 }
@@ -1,57 +1,115 @@
+use num::{BigInt, One, Zero};
+
 fn main() {
     let input = include_str!("../input.txt");
 
     let result1 = part1(input);
     println!("Part 1: {result1}");
-    assert_eq!(result1, 2075724761);
+    assert_eq!(result1, BigInt::from(2075724761));
 
     let result2 = part2(input);
     println!("Part 2: {result2}");
-    assert_eq!(result2, 1072);
+    assert_eq!(result2, BigInt::from(1072));
+
+    // With `--degrees`, report the highest degree of the fitted polynomials,
+    // and any sequences that never reach all-zero differences.
+    if std::env::args().any(|arg| arg == "--degrees") {
+        let polynomials: Vec<Polynomial> = parse_sequences(input).iter().map(|seq| Polynomial::fit(seq)).collect();
+        let max_degree = polynomials.iter().map(Polynomial::degree).max().unwrap_or(0);
+        println!("Highest degree: {max_degree}");
+        let unknown = polynomials.iter().filter(|p| !p.reaches_zero()).count();
+        if unknown > 0 {
+            println!("Warning: {unknown} sequences never reach all-zero differences");
+        }
+    }
 }
 
-fn part1(input: &str) -> i32 {
-    input
-        .lines()
-        .map(|line| {
-            line.split_ascii_whitespace()
-                .map(|word| word.parse::<i32>().unwrap())
-                .collect::<Vec<_>>()
-        })
-        .map(|seq| next_in_sequence(&seq))
+fn part1(input: &str) -> BigInt {
+    parse_sequences(input)
+        .iter()
+        .map(|seq| Polynomial::fit(seq).eval(seq.len() as i64))
         .sum()
 }
 
-fn part2(input: &str) -> i32 {
+fn part2(input: &str) -> BigInt {
+    parse_sequences(input)
+        .iter()
+        .map(|seq| Polynomial::fit(seq).eval(-1))
+        .sum()
+}
+
+fn parse_sequences(input: &str) -> Vec<Vec<BigInt>> {
     input
         .lines()
         .map(|line| {
             line.split_ascii_whitespace()
-                .map(|word| word.parse::<i32>().unwrap())
+                .map(|word| word.parse::<BigInt>().unwrap())
                 .collect::<Vec<_>>()
         })
-        .map(|seq| prev_in_sequence(&seq))
-        .sum()
+        .collect()
 }
 
-fn next_in_sequence(seq: &[i32]) -> i32 {
-    let diffs = seq.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
-    let next_diff = if diffs.iter().all(|&v| v == 0) {
-        0
-    } else {
-        next_in_sequence(&diffs)
-    };
-    seq.last().unwrap() + next_diff
+//
+// A sequence whose repeated differences eventually become all zero is a
+// polynomial in its index.  Newton's forward difference formula writes it as
+//
+//     p(k) = Σ Δʲ(0) * C(k, j)
+//
+// where Δʲ(0) is the first value in the j'th row of differences, and
+// C(k, j) = k(k-1)...(k-j+1) / j! is a binomial coefficient, which works
+// for negative k too.  So we only need the first value of each row.
+//
+#[derive(Debug)]
+struct Polynomial {
+    differences: Vec<BigInt>,
+    reaches_zero: bool,
 }
 
-fn prev_in_sequence(seq: &[i32]) -> i32 {
-    let diffs = seq.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
-    let prev_diff = if diffs.iter().all(|&v| v == 0) {
-        0
-    } else {
-        prev_in_sequence(&diffs)
-    };
-    seq.first().unwrap() - prev_diff
+impl Polynomial {
+    fn fit(seq: &[BigInt]) -> Self {
+        let mut differences = Vec::new();
+        let mut row = seq.to_vec();
+        let mut reaches_zero = false;
+        while !row.is_empty() {
+            if row.iter().all(|v| v.is_zero()) {
+                reaches_zero = true;
+                break;
+            }
+            differences.push(row[0].clone());
+            row = row.windows(2).map(|w| &w[1] - &w[0]).collect();
+        }
+        Polynomial { differences, reaches_zero }
+    }
+
+    // The degree of the polynomial (an all-zero sequence has degree 0).
+    fn degree(&self) -> usize {
+        self.differences
+            .iter()
+            .rposition(|v| !v.is_zero())
+            .unwrap_or(0)
+    }
+
+    // False if the differences never became all zero, so the sequence is
+    // too short to tell whether it really is a polynomial (of degree less
+    // than its length).  The result of `eval` is then just one guess.
+    fn reaches_zero(&self) -> bool {
+        self.reaches_zero
+    }
+
+    // The value at index k, where the first value in the sequence is index 0.
+    fn eval(&self, k: i64) -> BigInt {
+        let k = BigInt::from(k);
+        let mut binomial = BigInt::one();
+        let mut result = BigInt::zero();
+        for (j, diff) in self.differences.iter().enumerate() {
+            if j > 0 {
+                // C(k, j) = C(k, j-1) * (k - j + 1) / j, exactly
+                binomial = binomial * (&k - (j - 1)) / j;
+            }
+            result += diff * &binomial;
+        }
+        result
+    }
 }
 
 #[cfg(test)]
@@ -63,10 +121,52 @@ static EXAMPLE_INPUT: &str = "\
 
 #[test]
 fn test_part1_example() {
-    assert_eq!(part1(EXAMPLE_INPUT), 114);
+    assert_eq!(part1(EXAMPLE_INPUT), BigInt::from(114));
 }
 
 #[test]
 fn test_part2_example() {
-    assert_eq!(part2(EXAMPLE_INPUT), 2);
+    assert_eq!(part2(EXAMPLE_INPUT), BigInt::from(2));
+}
+
+#[cfg(test)]
+fn polynomial(s: &str) -> Polynomial {
+    let seq: Vec<BigInt> = s.split_ascii_whitespace().map(|word| word.parse().unwrap()).collect();
+    Polynomial::fit(&seq)
+}
+
+#[test]
+fn test_degree() {
+    assert_eq!(polynomial("0 3 6 9 12 15").degree(), 1);
+    assert_eq!(polynomial("1 3 6 10 15 21").degree(), 2);
+    assert_eq!(polynomial("10 13 16 21 30 45").degree(), 3);
+    assert_eq!(polynomial("7 7 7").degree(), 0);
+    assert_eq!(polynomial("0 0 0").degree(), 0);
+}
+
+#[test]
+fn test_eval_far_away() {
+    assert_eq!(polynomial("0 3 6 9 12 15").eval(1_000_000), BigInt::from(3_000_000));
+    // (k+1)(k+2)/2
+    assert_eq!(polynomial("1 3 6 10 15 21").eval(-1000), BigInt::from(498501));
+    // k³ overflows i32 and i64 would be close
+    let cube: BigInt = "1000000000000000000".parse().unwrap();
+    assert_eq!(polynomial("0 1 8 27 64").eval(1_000_000), cube);
+    assert_eq!(polynomial("0 1 8 27 64").eval(-1_000_000), -cube);
+}
+
+#[test]
+fn test_eval_matches_sequence() {
+    let p = polynomial("10 13 16 21 30 45");
+    let values: Vec<BigInt> = (0..6).map(|k| p.eval(k)).collect();
+    assert_eq!(values, [10, 13, 16, 21, 30, 45].map(BigInt::from));
+    assert_eq!(p.eval(6), BigInt::from(68));
+    assert_eq!(p.eval(-1), BigInt::from(5));
+}
+
+#[test]
+fn test_never_reaches_zero() {
+    assert!(polynomial("1 3 6 10 15 21").reaches_zero());
+    assert!(!polynomial("1 2 4 8 16").reaches_zero());
+    assert!(!polynomial("5").reaches_zero());
 }
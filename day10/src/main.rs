@@ -8,82 +8,63 @@ fn main() {
     let result2 = part2(input);
     println!("Part 2: {result2}");
     assert_eq!(result2, 483);
-}
 
-fn part1(input: &str) -> u32 {
-    // I think the trick here is to go around the loop until you get back
-    // to the starting position.  The answer is half of that distance.
+    // `--render` draws the map in the terminal; `--svg FILE` saves it as SVG
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--render") {
+        let pipe_loop = PipeLoop::parse(input).expect("valid map");
+        print!("{}", render_ansi(&pipe_loop));
+    }
+    if let Some(pos) = args.iter().position(|arg| arg == "--svg") {
+        let path = args.get(pos + 1).expect("a file name after --svg");
+        let pipe_loop = PipeLoop::parse(input).expect("valid map");
+        std::fs::write(path, render_svg(&pipe_loop)).expect("able to write SVG file");
+        println!("Wrote {path}");
+    }
 
-    // Parse the input into a Vec of Vecs (indexed as [row][col]).
-    let grid = input
-        .lines()
-        .map(|line| line.chars().collect::<Vec<char>>())
-        .collect::<Vec<_>>();
-    let num_rows = grid.len();
-    let num_cols = grid[0].len();
-    let mut start_row = 0;
-    let mut start_col = 0;
-    for (row, chars) in grid.iter().enumerate() {
-        for (col, ch) in chars.iter().enumerate() {
-            if ch == &'S' {
-                start_row = row;
-                start_col = col;
+    // `--bench` compares the speed of the ways of counting interior tiles
+    if std::env::args().any(|arg| arg == "--bench") {
+        const ITERATIONS: u32 = 100;
+        let pipe_loop = PipeLoop::parse(input).expect("valid map");
+        for method in [Interior::FloodFill, Interior::RayCasting, Interior::Shoelace] {
+            let start = std::time::Instant::now();
+            for _ in 0..ITERATIONS {
+                assert_eq!(count_interior(&pipe_loop, method), 483);
             }
+            println!("{method:?}: {:?} per iteration", start.elapsed() / ITERATIONS);
         }
     }
+}
 
-    let mut distance = 0;
-    let mut direction;
-    let mut row = start_row;
-    let mut col = start_col;
-
-    // Look at the neighbors of 'S' to find a connected pipe.
-    if row > 0 && "|7F".contains(grid[row-1][col]) {
-        direction = Dir::Up;
-    } else if col < num_cols-1 && "-J7".contains(grid[row][col+1]) {
-        direction = Dir::Right;
-    } else if row < num_rows-1 && "|LJ".contains(grid[row+1][col]) {
-        direction = Dir::Down;
-    } else if col > 0 && "-LF".contains(grid[row][col-1]) {
-        direction = Dir::Left;
-    } else {
-        panic!("No connected pipe found!")
-    }
+fn part1(input: &str) -> u32 {
+    // I think the trick here is to go around the loop until you get back
+    // to the starting position.  The answer is half of that distance.
+    let pipe_loop = PipeLoop::parse(input).expect("valid map");
+    pipe_loop.farthest().1 as u32
+}
 
-    loop {
-        // Move to the neighbor in direction `direction`
-        match direction {
-            Dir::Up => row -= 1,
-            Dir::Down => row += 1,
-            Dir::Left => col -= 1,
-            Dir::Right => col += 1,
-        };
-        distance += 1;
-        if row == start_row && col == start_col {
-            break;
-        }
+fn part2(input: &str) -> u32 {
+    let pipe_loop = PipeLoop::parse(input).expect("valid map");
+    count_interior(&pipe_loop, Interior::FloodFill)
+}
 
-        // Figure out the new direction to leave neighbor
-        direction = match (direction, grid[row][col]) {
-            (Dir::Up, '|') => Dir::Up,
-            (Dir::Up, '7') => Dir::Left,
-            (Dir::Up, 'F') => Dir::Right,
-            (Dir::Down, '|') => Dir::Down,
-            (Dir::Down, 'L') => Dir::Right,
-            (Dir::Down, 'J') => Dir::Left,
-            (Dir::Left, '-') => Dir::Left,
-            (Dir::Left, 'L') => Dir::Up,
-            (Dir::Left, 'F') => Dir::Down,
-            (Dir::Right, '-') => Dir::Right,
-            (Dir::Right, 'J') => Dir::Up,
-            (Dir::Right, '7') => Dir::Down,
-            _ => panic!("Invalid direction & char")
-        };
+// Ways of counting the tiles enclosed by the loop
+#[derive(Debug, Clone, Copy)]
+enum Interior {
+    FloodFill,
+    RayCasting,
+    Shoelace,
+}
+
+fn count_interior(pipe_loop: &PipeLoop, method: Interior) -> u32 {
+    match method {
+        Interior::FloodFill => flood_fill(pipe_loop),
+        Interior::RayCasting => ray_casting(pipe_loop),
+        Interior::Shoelace => shoelace(pipe_loop),
     }
-    distance / 2
 }
 
-fn part2(input: &str) -> u32 {
+fn flood_fill(pipe_loop: &PipeLoop) -> u32 {
     //
     // I think the solution here is to make a new grid with just the
     // loop marked.  Then pick a location outside the bounds of the
@@ -98,24 +79,8 @@ fn part2(input: &str) -> u32 {
     // so that I don't have to bother checking whether coordinates are
     // in bounds.
     //
-
-    // Parse the input into a Vec of Vecs (indexed as [row][col]).
-    let grid = input
-        .lines()
-        .map(|line| line.chars().collect::<Vec<char>>())
-        .collect::<Vec<_>>();
-    let num_rows = grid.len();
-    let num_cols = grid[0].len();
-    let mut start_row = 0;
-    let mut start_col = 0;
-    for (row, chars) in grid.iter().enumerate() {
-        for (col, ch) in chars.iter().enumerate() {
-            if ch == &'S' {
-                start_row = row;
-                start_col = col;
-            }
-        }
-    }
+    let num_rows = pipe_loop.num_rows();
+    let num_cols = pipe_loop.num_cols();
 
     // Make the "zoomed in" grid where we mark the pipes, and locations
     // known to be exterior to the loop.  The dimensions are 2N+3, where
@@ -124,82 +89,21 @@ fn part2(input: &str) -> u32 {
         vec![false; 2*num_cols+3]
     }).collect::<Vec<_>>();
     // Mark the outermost rows and columns
-    for col in 0..(2*num_cols+3) {
-        marks[0][col] = true;
-        marks[2*num_rows+2][col] = true;
-    }
+    marks[0].fill(true);
+    marks[2*num_rows+2].fill(true);
     #[allow(clippy::needless_range_loop)]
     for row in 1..(2*num_rows+2) {
         marks[row][0] = true;
         marks[row][2*num_cols+2] = true;
     }
 
-    // Follow the loop, like in part 1, marking off the pipe locations.
-    // A pipe at [row][col] in the original grid will be located at
-    // [2*row+2][2*col+2].  Every move needs to mark two squares in
-    // the given direction.
-    marks[2*start_row+2][2*start_col+2] = true;
-    let mut direction;
-    let mut row = start_row;
-    let mut col = start_col;
-
-    // Look at the neighbors of 'S' to find a connected pipe.
-    if row > 0 && "|7F".contains(grid[row-1][col]) {
-        direction = Dir::Up;
-    } else if col < num_cols-1 && "-J7".contains(grid[row][col+1]) {
-        direction = Dir::Right;
-    } else if row < num_rows-1 && "|LJ".contains(grid[row+1][col]) {
-        direction = Dir::Down;
-    } else if col > 0 && "-LF".contains(grid[row][col-1]) {
-        direction = Dir::Left;
-    } else {
-        panic!("No connected pipe found!")
-    }
-
-    loop {
-        // Move to the neighbor in direction `direction`
-        match direction {
-            Dir::Up => {
-                marks[2*row+1][2*col+2] = true;
-                marks[2*row][2*col+2] = true;
-                row -= 1;
-            },
-            Dir::Down => {
-                marks[2*row+3][2*col+2] = true;
-                marks[2*row+4][2*col+2] = true;
-                row += 1;
-            },
-            Dir::Left => {
-                marks[2*row+2][2*col+1] = true;
-                marks[2*row+2][2*col] = true;
-                col -= 1;
-            },
-            Dir::Right => {
-                marks[2*row+2][2*col+3] = true;
-                marks[2*row+2][2*col+4] = true;
-                col += 1;
-            },
-        };
-        if row == start_row && col == start_col {
-            break;
-        }
-
-        // Figure out the new direction to leave neighbor
-        direction = match (direction, grid[row][col]) {
-            (Dir::Up, '|') => Dir::Up,
-            (Dir::Up, '7') => Dir::Left,
-            (Dir::Up, 'F') => Dir::Right,
-            (Dir::Down, '|') => Dir::Down,
-            (Dir::Down, 'L') => Dir::Right,
-            (Dir::Down, 'J') => Dir::Left,
-            (Dir::Left, '-') => Dir::Left,
-            (Dir::Left, 'L') => Dir::Up,
-            (Dir::Left, 'F') => Dir::Down,
-            (Dir::Right, '-') => Dir::Right,
-            (Dir::Right, 'J') => Dir::Up,
-            (Dir::Right, '7') => Dir::Down,
-            _ => panic!("Invalid direction & char")
-        };
+    // Mark off the pipe locations.  A pipe at [row][col] in the original
+    // grid will be located at [2*row+2][2*col+2].  Every move along the
+    // loop also marks the square between the two pipes.
+    let path = pipe_loop.path();
+    for (&(r1, c1), &(r2, c2)) in path.iter().zip(path.iter().cycle().skip(1)) {
+        marks[2*r1+2][2*c1+2] = true;
+        marks[r1+r2+2][c1+c2+2] = true;
     }
 
     // Now pick a location outside the loop's bounds (that MUST be
@@ -234,6 +138,391 @@ fn mark_outside(marks: &mut [Vec<bool>]) {
     }
 }
 
+//
+// Cast a horizontal ray along each row, just below the midline of the row.
+// It crosses the loop at every "|", "F" or "7" on the loop (but not at
+// "-", "L" or "J", which only touch the top half of the row).  A tile that
+// is not part of the loop is inside if the ray has made an odd number of
+// crossings to get there.
+//
+fn ray_casting(pipe_loop: &PipeLoop) -> u32 {
+    regions(pipe_loop)
+        .iter()
+        .flatten()
+        .filter(|&&region| region == Region::Inside)
+        .count() as u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+    Loop,
+    Inside,
+    Outside,
+}
+
+// Classify every tile (indexed as [row][col]) by ray casting
+fn regions(pipe_loop: &PipeLoop) -> Vec<Vec<Region>> {
+    pipe_loop
+        .on_loop()
+        .iter()
+        .enumerate()
+        .map(|(row, on_loop)| {
+            let mut crossings = 0;
+            on_loop
+                .iter()
+                .enumerate()
+                .map(|(col, &on_loop)| {
+                    if on_loop {
+                        if "|F7".contains(pipe_loop.tile(row, col)) {
+                            crossings += 1;
+                        }
+                        Region::Loop
+                    } else if crossings % 2 == 1 {
+                        Region::Inside
+                    } else {
+                        Region::Outside
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+//
+// The shoelace formula gives the area of the polygon whose vertices are the
+// centers of the loop's tiles.  Pick's theorem relates that area to the
+// number of interior and boundary points:  A = I + B/2 - 1.  The boundary
+// points are the loop tiles, so I = A - B/2 + 1.
+//
+fn shoelace(pipe_loop: &PipeLoop) -> u32 {
+    // Only the corners matter to the shoelace formula
+    let vertices: Vec<(i64, i64)> = pipe_loop
+        .path()
+        .iter()
+        .filter(|&&(row, col)| "LJ7F".contains(pipe_loop.tile(row, col)))
+        .map(|&(row, col)| (row as i64, col as i64))
+        .collect();
+    let twice_area: i64 = vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(&(r1, c1), &(r2, c2))| c1 * r2 - c2 * r1)
+        .sum::<i64>()
+        .abs();
+    let boundary = pipe_loop.len() as i64;
+    ((twice_area - boundary) / 2 + 1) as u32
+}
+
+// Pipes on the loop are drawn heavy, and junk pipes are drawn light.
+fn box_drawing(ch: char, on_loop: bool) -> char {
+    match (ch, on_loop) {
+        ('|', true) => '┃',
+        ('-', true) => '━',
+        ('L', true) => '┗',
+        ('J', true) => '┛',
+        ('7', true) => '┓',
+        ('F', true) => '┏',
+        ('|', false) => '│',
+        ('-', false) => '─',
+        ('L', false) => '└',
+        ('J', false) => '┘',
+        ('7', false) => '┐',
+        ('F', false) => '┌',
+        _ => ' ',
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_LOOP: &str = "\x1b[0;1;33m";        // bold yellow
+const ANSI_START: &str = "\x1b[0;1;31m";       // bold red
+const ANSI_INSIDE: &str = "\x1b[0;2;30;42m";   // dim, on green
+const ANSI_OUTSIDE: &str = "\x1b[0;2;37;44m";  // dim, on blue
+
+// Draw the map for a terminal, using box-drawing characters for pipes.
+// The loop is bold, 'S' is red, and the other tiles are dimmed, with a
+// green background inside the loop and blue outside.
+fn render_ansi(pipe_loop: &PipeLoop) -> String {
+    let mut result = String::new();
+    for (row, regions) in regions(pipe_loop).iter().enumerate() {
+        for (col, &region) in regions.iter().enumerate() {
+            let color = match region {
+                Region::Loop if (row, col) == pipe_loop.path()[0] => ANSI_START,
+                Region::Loop => ANSI_LOOP,
+                Region::Inside => ANSI_INSIDE,
+                Region::Outside => ANSI_OUTSIDE,
+            };
+            let glyph = box_drawing(pipe_loop.tile(row, col), region == Region::Loop);
+            result.push_str(color);
+            result.push(glyph);
+        }
+        result.push_str(ANSI_RESET);
+        result.push('\n');
+    }
+    result
+}
+
+// The same picture as `render_ansi`, as an SVG image.  Each tile is a
+// square, and pipes are lines between the midpoints of its edges.
+fn render_svg(pipe_loop: &PipeLoop) -> String {
+    const SIZE: usize = 10;
+    const HALF: usize = SIZE / 2;
+    let width = pipe_loop.num_cols() * SIZE;
+    let height = pipe_loop.num_rows() * SIZE;
+    let mut result = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    );
+    result.push_str(&format!("<rect width=\"{width}\" height=\"{height}\" fill=\"#2a4d8f\"/>\n"));
+
+    let regions = regions(pipe_loop);
+    for (row, regions) in regions.iter().enumerate() {
+        for (col, &region) in regions.iter().enumerate() {
+            let (x, y) = (col * SIZE, row * SIZE);
+            if region == Region::Inside {
+                result.push_str(&format!("<rect x=\"{x}\" y=\"{y}\" width=\"{SIZE}\" height=\"{SIZE}\" fill=\"#3c9a4a\"/>\n"));
+            }
+            if region != Region::Loop {
+                // Junk pipe, drawn as two half-segments from the center
+                let (cx, cy) = (x + HALF, y + HALF);
+                let ends: &[(usize, usize)] = match pipe_loop.tile(row, col) {
+                    '|' => &[(cx, y), (cx, y + SIZE)],
+                    '-' => &[(x, cy), (x + SIZE, cy)],
+                    'L' => &[(cx, y), (x + SIZE, cy)],
+                    'J' => &[(cx, y), (x, cy)],
+                    '7' => &[(x, cy), (cx, y + SIZE)],
+                    'F' => &[(x + SIZE, cy), (cx, y + SIZE)],
+                    _ => &[],
+                };
+                for &(ex, ey) in ends {
+                    result.push_str(&format!("<line x1=\"{cx}\" y1=\"{cy}\" x2=\"{ex}\" y2=\"{ey}\" stroke=\"#9aa5b1\" stroke-width=\"1\"/>\n"));
+                }
+            }
+        }
+    }
+
+    let points: Vec<String> = pipe_loop
+        .path()
+        .iter()
+        .map(|&(row, col)| format!("{},{}", col * SIZE + HALF, row * SIZE + HALF))
+        .collect();
+    result.push_str(&format!(
+        "<polygon points=\"{}\" fill=\"none\" stroke=\"#f2c94c\" stroke-width=\"3\" stroke-linejoin=\"round\"/>\n",
+        points.join(" ")
+    ));
+    let (row, col) = pipe_loop.path()[0];
+    result.push_str(&format!(
+        "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"#e74c3c\"/>\n",
+        col * SIZE + HALF, row * SIZE + HALF, HALF - 1
+    ));
+    result.push_str("</svg>\n");
+    result
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum MapError {
+    Empty,
+    RaggedRow { row: usize },
+    NoStart,
+    MultipleStarts,
+    NoConnectedPipe,
+    LeavesMap { row: usize, col: usize },
+    BrokenPipe { row: usize, col: usize, ch: char },
+}
+
+impl std::fmt::Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapError::Empty => write!(f, "the map is empty"),
+            MapError::RaggedRow { row } => write!(f, "row {row} is not the same length as row 0"),
+            MapError::NoStart => write!(f, "no 'S' in the map"),
+            MapError::MultipleStarts => write!(f, "more than one 'S' in the map"),
+            MapError::NoConnectedPipe => write!(f, "no pipe connects to 'S'"),
+            MapError::LeavesMap { row, col } => write!(f, "the pipe at row {row}, column {col} leads off the map"),
+            MapError::BrokenPipe { row, col, ch } => write!(f, "the loop runs into {ch:?} at row {row}, column {col}"),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+// The loop of pipe through 'S', found by following the pipes until we
+// get back to 'S'.
+struct PipeLoop {
+    grid: Vec<Vec<char>>,
+    path: Vec<(usize, usize)>,
+    start_shape: char,
+}
+
+impl PipeLoop {
+    fn parse(input: &str) -> Result<Self, MapError> {
+        // Parse the input into a Vec of Vecs (indexed as [row][col]).
+        let grid = input
+            .lines()
+            .map(|line| line.chars().collect::<Vec<char>>())
+            .collect::<Vec<_>>();
+        if grid.is_empty() || grid[0].is_empty() {
+            return Err(MapError::Empty);
+        }
+        if let Some(row) = grid.iter().position(|chars| chars.len() != grid[0].len()) {
+            return Err(MapError::RaggedRow { row });
+        }
+
+        let mut starts = grid
+            .iter()
+            .enumerate()
+            .flat_map(|(row, chars)| {
+                chars.iter().enumerate().filter(|(_, &ch)| ch == 'S').map(move |(col, _)| (row, col))
+            });
+        let start = starts.next().ok_or(MapError::NoStart)?;
+        if starts.next().is_some() {
+            return Err(MapError::MultipleStarts);
+        }
+
+        // Try each neighbor of 'S' that has a pipe pointing back at 'S'.
+        // Junk pipe might point at 'S' without being part of the loop, so
+        // keep the first one that leads back to 'S'.
+        let (row, col) = start;
+        let num_rows = grid.len();
+        let num_cols = grid[0].len();
+        let candidates = [
+            (Dir::Up, row > 0 && "|7F".contains(grid[row-1][col])),
+            (Dir::Right, col < num_cols-1 && "-J7".contains(grid[row][col+1])),
+            (Dir::Down, row < num_rows-1 && "|LJ".contains(grid[row+1][col])),
+            (Dir::Left, col > 0 && "-LF".contains(grid[row][col-1])),
+        ];
+        let mut first_error = None;
+        for (direction, connected) in candidates {
+            if !connected {
+                continue;
+            }
+            match Self::trace(&grid, start, direction) {
+                Ok((path, start_shape)) => return Ok(PipeLoop { grid, path, start_shape }),
+                Err(e) => { first_error.get_or_insert(e); }
+            }
+        }
+        Err(first_error.unwrap_or(MapError::NoConnectedPipe))
+    }
+
+    // Follow the pipes, leaving `start` in `direction`, until we get back
+    // to `start`.  Returns the path and the shape of the pipe at `start`.
+    fn trace(grid: &[Vec<char>], start: (usize, usize), mut direction: Dir) -> Result<(Vec<(usize, usize)>, char), MapError> {
+        let num_rows = grid.len();
+        let num_cols = grid[0].len();
+        let first_direction = direction;
+        let (mut row, mut col) = start;
+        let mut path = vec![start];
+        loop {
+            // Move to the neighbor in direction `direction`
+            let off_map = match direction {
+                Dir::Up => row == 0,
+                Dir::Down => row == num_rows - 1,
+                Dir::Left => col == 0,
+                Dir::Right => col == num_cols - 1,
+            };
+            if off_map {
+                return Err(MapError::LeavesMap { row, col });
+            }
+            match direction {
+                Dir::Up => row -= 1,
+                Dir::Down => row += 1,
+                Dir::Left => col -= 1,
+                Dir::Right => col += 1,
+            };
+            if (row, col) == start {
+                break;
+            }
+            path.push((row, col));
+
+            // Figure out the new direction to leave neighbor
+            let ch = grid[row][col];
+            direction = next_direction(direction, ch).ok_or(MapError::BrokenPipe { row, col, ch })?;
+        }
+
+        // 'S' connects the way we left it, and back the way we arrived.
+        let start_shape = match (first_direction, direction) {
+            (Dir::Up, Dir::Up) => '|',
+            (Dir::Up, Dir::Left) => 'L',
+            (Dir::Up, Dir::Right) => 'J',
+            (Dir::Right, Dir::Right) => '-',
+            (Dir::Right, Dir::Up) => 'F',
+            (Dir::Right, Dir::Down) => 'L',
+            (Dir::Down, Dir::Down) => '|',
+            (Dir::Down, Dir::Left) => 'F',
+            (Dir::Down, Dir::Right) => '7',
+            (Dir::Left, Dir::Left) => '-',
+            (Dir::Left, Dir::Up) => '7',
+            (Dir::Left, Dir::Down) => 'J',
+            // Arriving back the way we left means the "loop" is a dead end
+            _ => return Err(MapError::BrokenPipe { row, col, ch: 'S' }),
+        };
+        Ok((path, start_shape))
+    }
+
+    // The (row, col) of every tile on the loop, in order, starting at 'S'
+    fn path(&self) -> &[(usize, usize)] {
+        &self.path
+    }
+
+    fn len(&self) -> usize {
+        self.path.len()
+    }
+
+    // The shape of the pipe hidden under 'S'
+    fn start_shape(&self) -> char {
+        self.start_shape
+    }
+
+    // The point on the loop farthest from 'S', and how many steps away it is
+    fn farthest(&self) -> ((usize, usize), usize) {
+        let distance = self.len() / 2;
+        (self.path[distance], distance)
+    }
+
+    fn num_rows(&self) -> usize {
+        self.grid.len()
+    }
+
+    fn num_cols(&self) -> usize {
+        self.grid[0].len()
+    }
+
+    // The character at the given location, with 'S' replaced by its shape
+    fn tile(&self, row: usize, col: usize) -> char {
+        if (row, col) == self.path[0] {
+            self.start_shape()
+        } else {
+            self.grid[row][col]
+        }
+    }
+
+    // A grid (indexed as [row][col]) that is true for tiles on the loop
+    fn on_loop(&self) -> Vec<Vec<bool>> {
+        let mut on_loop = vec![vec![false; self.num_cols()]; self.num_rows()];
+        for &(row, col) in self.path.iter() {
+            on_loop[row][col] = true;
+        }
+        on_loop
+    }
+}
+
+// Figure out the new direction to leave a pipe we entered moving `direction`
+fn next_direction(direction: Dir, ch: char) -> Option<Dir> {
+    match (direction, ch) {
+        (Dir::Up, '|') => Some(Dir::Up),
+        (Dir::Up, '7') => Some(Dir::Left),
+        (Dir::Up, 'F') => Some(Dir::Right),
+        (Dir::Down, '|') => Some(Dir::Down),
+        (Dir::Down, 'L') => Some(Dir::Right),
+        (Dir::Down, 'J') => Some(Dir::Left),
+        (Dir::Left, '-') => Some(Dir::Left),
+        (Dir::Left, 'L') => Some(Dir::Up),
+        (Dir::Left, 'F') => Some(Dir::Down),
+        (Dir::Right, '-') => Some(Dir::Right),
+        (Dir::Right, 'J') => Some(Dir::Up),
+        (Dir::Right, '7') => Some(Dir::Down),
+        _ => None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 enum Dir {
     Up,
     Right,
@@ -342,3 +631,86 @@ fn test_part2_ex5() {
 fn test_part2_ex6() {
     assert_eq!(part2(EXAMPLE6), 10);
 }
+
+#[test]
+fn test_interior_methods_agree() {
+    for example in [EXAMPLE1, EXAMPLE2, EXAMPLE3, EXAMPLE4, EXAMPLE5, EXAMPLE6] {
+        let pipe_loop = PipeLoop::parse(example).unwrap();
+        let expected = count_interior(&pipe_loop, Interior::FloodFill);
+        assert_eq!(count_interior(&pipe_loop, Interior::RayCasting), expected);
+        assert_eq!(count_interior(&pipe_loop, Interior::Shoelace), expected);
+    }
+}
+
+#[test]
+fn test_start_shape() {
+    assert_eq!(PipeLoop::parse(EXAMPLE1).unwrap().start_shape(), 'F');
+    assert_eq!(PipeLoop::parse(EXAMPLE2).unwrap().start_shape(), 'F');
+    assert_eq!(PipeLoop::parse(EXAMPLE5).unwrap().start_shape(), 'F');
+    assert_eq!(PipeLoop::parse(EXAMPLE6).unwrap().start_shape(), '7');
+}
+
+#[test]
+fn test_pipe_loop() {
+    let pipe_loop = PipeLoop::parse(EXAMPLE2).unwrap();
+    assert_eq!(pipe_loop.len(), 16);
+    assert_eq!(pipe_loop.path()[0], (2, 0));
+    assert_eq!(pipe_loop.farthest(), ((2, 4), 8));
+}
+
+#[test]
+fn test_malformed_maps() {
+    assert_eq!(PipeLoop::parse("").err(), Some(MapError::Empty));
+    assert_eq!(PipeLoop::parse("...\n..\n").err(), Some(MapError::RaggedRow { row: 1 }));
+    assert_eq!(PipeLoop::parse("F-7\n|.|\nL-J\n").err(), Some(MapError::NoStart));
+    assert_eq!(PipeLoop::parse("S-S\n").err(), Some(MapError::MultipleStarts));
+    assert_eq!(PipeLoop::parse("...\n.S.\n...\n").err(), Some(MapError::NoConnectedPipe));
+    assert_eq!(PipeLoop::parse("S-7\n|.|\nL-.\n").err(), Some(MapError::BrokenPipe { row: 2, col: 2, ch: '.' }));
+    assert_eq!(PipeLoop::parse("S--\n|..\nL--\n").err(), Some(MapError::LeavesMap { row: 0, col: 2 }));
+}
+
+#[test]
+fn test_render_ansi() {
+    let pipe_loop = PipeLoop::parse(EXAMPLE4).unwrap();
+    let rendered = render_ansi(&pipe_loop);
+    assert_eq!(rendered.matches(ANSI_INSIDE).count(), 4);
+    assert_eq!(rendered.matches(ANSI_START).count(), 1);
+
+    // Without the colors, it's just the box-drawing picture
+    let mut plain = rendered;
+    for code in [ANSI_RESET, ANSI_LOOP, ANSI_START, ANSI_INSIDE, ANSI_OUTSIDE] {
+        plain = plain.replace(code, "");
+    }
+    assert_eq!(plain.lines().collect::<Vec<_>>(), vec![
+        "          ",
+        " ┏━━━━━━┓ ",
+        " ┃┏━━━━┓┃ ",
+        " ┃┃    ┃┃ ",
+        " ┃┃    ┃┃ ",
+        " ┃┗━┓┏━┛┃ ",
+        " ┃  ┃┃  ┃ ",
+        " ┗━━┛┗━━┛ ",
+        "          ",
+    ]);
+}
+
+#[test]
+fn test_render_junk_pipes() {
+    let pipe_loop = PipeLoop::parse(EXAMPLE1).unwrap();
+    let mut plain = render_ansi(&pipe_loop);
+    for code in [ANSI_RESET, ANSI_LOOP, ANSI_START, ANSI_INSIDE, ANSI_OUTSIDE] {
+        plain = plain.replace(code, "");
+    }
+    assert_eq!(plain.lines().next(), Some("─└│┌┐"));
+    assert_eq!(plain.lines().nth(1), Some("┐┏━┓│"));
+}
+
+#[test]
+fn test_render_svg() {
+    let pipe_loop = PipeLoop::parse(EXAMPLE4).unwrap();
+    let svg = render_svg(&pipe_loop);
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("fill=\"#3c9a4a\"").count(), 4);
+    assert_eq!(svg.matches("<polygon ").count(), 1);
+}
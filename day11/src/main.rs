@@ -1,5 +1,5 @@
 use itertools::Itertools;
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 
 fn main() {
     let input = include_str!("../input.txt");
@@ -11,91 +11,154 @@ fn main() {
     let result2 = part2(input);
     println!("Part 2: {result2}");
     assert_eq!(result2, 543018317006);
-}
 
-fn part1(input: &str) -> usize {
-    let mut galaxies = Vec::<(usize, usize)>::new(); // (row, col)
-    let mut occupied_rows = HashSet::<usize>::new();
-    let mut occupied_cols = HashSet::<usize>::new();
-
-    // Parse the input.  Find the location of all of the galaxies.
-    // While we're parsing, also make note of which rows and columns
-    // contain a galaxy.
-    for (row, line) in input.lines().enumerate() {
-        for (col, ch) in line.chars().enumerate() {
-            if ch == '#' {
-                galaxies.push((row, col));
-                occupied_rows.insert(row);
-                occupied_cols.insert(col);
-            }
+    // `--report [EXPANSION]` lists each galaxy's nearest and farthest
+    // neighbors, and a histogram of the distances between pairs.  The
+    // expansion defaults to part 1's.
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|arg| arg == "--report") {
+        let expansion = args.get(pos + 1).and_then(|arg| arg.parse().ok()).unwrap_or(2);
+        let universe = Universe::new(&parse_galaxies(input), expansion);
+        for (number, neighbors) in universe.neighbors().iter().enumerate() {
+            let number = number + 1;
+            let ((nearest, near), (farthest, far)) = neighbors;
+            println!("Galaxy {number}: nearest {nearest} ({near}), farthest {farthest} ({far})");
+        }
+        for (distance, count) in universe.histogram() {
+            println!("Distance {distance}: {count} pairs");
         }
     }
+}
+
+fn part1(input: &str) -> usize {
+    sum_distances(&parse_galaxies(input), 2)
+}
 
-    // Find the Manhattan distance between each pair of galaxies,
-    // adjusted for empty rows and columns between them.  And sum
-    // those all up.
+fn part2(input: &str) -> usize {
+    sum_distances(&parse_galaxies(input), 1_000_000)
+}
+
+// Find the location of all of the galaxies, as (row, col).
+fn parse_galaxies(input: &str) -> Vec<(usize, usize)> {
+    input
+        .lines()
+        .enumerate()
+        .flat_map(|(row, line)| {
+            line.char_indices()
+                .filter(|&(_, ch)| ch == '#')
+                .map(move |(col, _)| (row, col))
+        })
+        .collect()
+}
+
+// Sum of the Manhattan distances between every pair of galaxies, after
+// every empty row and column has been replaced by `expansion` of them.
+fn sum_distances(galaxies: &[(usize, usize)], expansion: usize) -> usize {
+    let galaxies = expand(galaxies, expansion);
+    let rows = galaxies.iter().map(|&(row, _)| row).collect();
+    let cols = galaxies.iter().map(|&(_, col)| col).collect();
+    sum_pairwise(rows) + sum_pairwise(cols)
+}
+
+// Move the galaxies to where they are after expansion.  Rather than
+// looking at every row and column between each pair of galaxies, adjust
+// all of the row and column numbers up front.
+fn expand(galaxies: &[(usize, usize)], expansion: usize) -> Vec<(usize, usize)> {
+    assert!(expansion > 0, "expansion factor must be at least 1");
+    let new_rows = expanded_coordinates(galaxies.iter().map(|&(row, _)| row), expansion);
+    let new_cols = expanded_coordinates(galaxies.iter().map(|&(_, col)| col), expansion);
     galaxies
         .iter()
-        .combinations(2)
-        .map(|pair| {
-            let (min_row, max_row) = minmax(pair[0].0, pair[1].0);
-            let (min_col, max_col) = minmax(pair[0].1, pair[1].1);
-
-            max_row - min_row + max_col - min_col
-                + (min_row..max_row)
-                    .map(|row| if occupied_rows.contains(&row) { 0 } else { 1 })
-                    .sum::<usize>()
-                + (min_col..max_col)
-                    .map(|row| if occupied_cols.contains(&row) { 0 } else { 1 })
-                    .sum::<usize>()
-        })
-        .sum()
+        .map(|&(row, col)| (new_rows[row], new_cols[col]))
+        .collect()
 }
 
-fn part2(input: &str) -> usize {
-    let mut galaxies = Vec::<(usize, usize)>::new(); // (row, col)
-    let mut occupied_rows = HashSet::<usize>::new();
-    let mut occupied_cols = HashSet::<usize>::new();
-
-    // Parse the input.  Find the location of all of the galaxies.
-    // While we're parsing, also make note of which rows and columns
-    // contain a galaxy.
-    for (row, line) in input.lines().enumerate() {
-        for (col, ch) in line.chars().enumerate() {
-            if ch == '#' {
-                galaxies.push((row, col));
-                occupied_rows.insert(row);
-                occupied_cols.insert(col);
-            }
-        }
+// Given the occupied coordinates along one axis, returns the new coordinate
+// for every old one, indexed by the old coordinate.  The number of empty
+// rows (or columns) before each one is a prefix sum.
+fn expanded_coordinates(occupied: impl Iterator<Item = usize>, expansion: usize) -> Vec<usize> {
+    let occupied = occupied.collect_vec();
+    let size = occupied.iter().max().map_or(0, |&max| max + 1);
+    let mut is_occupied = vec![false; size];
+    for coord in occupied {
+        is_occupied[coord] = true;
     }
 
-    // Find the Manhattan distance between each pair of galaxies,
-    // adjusted for empty rows and columns between them.  And sum
-    // those all up.
-    galaxies
+    let mut empty_before = 0;
+    is_occupied
+        .into_iter()
+        .enumerate()
+        .map(|(coord, occupied)| {
+            let new_coord = coord + empty_before * (expansion - 1);
+            if !occupied {
+                empty_before += 1;
+            }
+            new_coord
+        })
+        .collect()
+}
+
+// Sum of |a - b| over every pair.  Once sorted, each value is larger than
+// all of the values before it, so it contributes value * index minus the
+// sum of the values before it.
+fn sum_pairwise(mut values: Vec<usize>) -> usize {
+    values.sort_unstable();
+    let mut prefix_sum = 0;
+    values
         .iter()
-        .combinations(2)
-        .map(|pair| {
-            let (min_row, max_row) = minmax(pair[0].0, pair[1].0);
-            let (min_col, max_col) = minmax(pair[0].1, pair[1].1);
-
-            max_row - min_row + max_col - min_col
-                + (min_row..max_row)
-                    .map(|row| if occupied_rows.contains(&row) { 0 } else { 1 })
-                    .sum::<usize>() * 999999
-                + (min_col..max_col)
-                    .map(|row| if occupied_cols.contains(&row) { 0 } else { 1 })
-                    .sum::<usize>() * 999999
+        .enumerate()
+        .map(|(index, &value)| {
+            let contribution = value * index - prefix_sum;
+            prefix_sum += value;
+            contribution
         })
         .sum()
 }
 
-fn minmax(a: usize, b: usize) -> (usize, usize) {
-    if a < b {
-        (a, b)
-    } else {
-        (b, a)
+// The galaxies after expansion, numbered from 1 in reading order, the way
+// the puzzle numbers them.
+struct Universe {
+    galaxies: Vec<(usize, usize)>,
+}
+
+impl Universe {
+    fn new(galaxies: &[(usize, usize)], expansion: usize) -> Self {
+        Universe { galaxies: expand(galaxies, expansion) }
+    }
+
+    // Distance between galaxies numbered `a` and `b` (starting from 1)
+    fn distance(&self, a: usize, b: usize) -> usize {
+        let (r1, c1) = self.galaxies[a - 1];
+        let (r2, c2) = self.galaxies[b - 1];
+        r1.abs_diff(r2) + c1.abs_diff(c2)
+    }
+
+    // For each galaxy, the number of and distance to its nearest and farthest
+    // galaxies.  Ties go to the lower numbered galaxy.
+    fn neighbors(&self) -> Vec<((usize, usize), (usize, usize))> {
+        let numbers = 1..=self.galaxies.len();
+        numbers
+            .clone()
+            .map(|a| {
+                let distances = numbers
+                    .clone()
+                    .filter(|&b| b != a)
+                    .map(|b| (b, self.distance(a, b)))
+                    .collect_vec();
+                let nearest = *distances.iter().min_by_key(|(_, distance)| distance).expect("another galaxy");
+                let farthest = *distances.iter().rev().max_by_key(|(_, distance)| distance).expect("another galaxy");
+                (nearest, farthest)
+            })
+            .collect()
+    }
+
+    // How many pairs of galaxies are each distance apart
+    fn histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for (a, b) in (1..=self.galaxies.len()).tuple_combinations() {
+            *histogram.entry(self.distance(a, b)).or_default() += 1;
+        }
+        histogram
     }
 }
 
@@ -117,3 +180,67 @@ static EXAMPLE1: &str = "\
 fn test_part1() {
     assert_eq!(part1(EXAMPLE1), 374);
 }
+
+#[test]
+fn test_expansion_factors() {
+    let galaxies = parse_galaxies(EXAMPLE1);
+    assert_eq!(sum_distances(&galaxies, 10), 1030);
+    assert_eq!(sum_distances(&galaxies, 100), 8410);
+}
+
+#[test]
+fn test_matches_every_pair() {
+    let galaxies = parse_galaxies(EXAMPLE1);
+    let expanded = expand(&galaxies, 7);
+    let expected: usize = expanded
+        .iter()
+        .tuple_combinations()
+        .map(|(a, b)| a.0.abs_diff(b.0) + a.1.abs_diff(b.1))
+        .sum();
+    assert_eq!(sum_distances(&galaxies, 7), expected);
+}
+
+#[test]
+fn test_many_galaxies() {
+    // 100,000 galaxies on a diagonal, so no empty rows or columns.
+    // Each pair at distance d apart (in rows and columns) adds 2*d, and
+    // there are n-d such pairs: 2 * Σ d(n-d) = (n³ - n) / 3.
+    let n = 100_000;
+    let galaxies = (0..n).map(|i| (i, i)).collect_vec();
+    assert_eq!(sum_distances(&galaxies, 1_000_000), (n * n * n - n) / 3);
+}
+
+#[test]
+fn test_pair_distances() {
+    // The examples from the puzzle description
+    let universe = Universe::new(&parse_galaxies(EXAMPLE1), 2);
+    assert_eq!(universe.distance(5, 9), 9);
+    assert_eq!(universe.distance(1, 7), 15);
+    assert_eq!(universe.distance(3, 6), 17);
+    assert_eq!(universe.distance(8, 9), 5);
+    assert_eq!(universe.distance(9, 8), 5);
+}
+
+#[test]
+fn test_neighbors() {
+    let universe = Universe::new(&parse_galaxies(EXAMPLE1), 2);
+    let neighbors = universe.neighbors();
+    assert_eq!(neighbors.len(), 9);
+    assert_eq!(neighbors[0], ((2, 6), (6, 15)));
+    assert_eq!(neighbors[7], ((9, 5), (2, 19)));
+}
+
+#[test]
+fn test_histogram() {
+    let universe = Universe::new(&parse_galaxies(EXAMPLE1), 2);
+    let histogram = universe.histogram();
+    assert_eq!(histogram.values().sum::<usize>(), 36);
+    assert_eq!(histogram.iter().map(|(distance, count)| distance * count).sum::<usize>(), 374);
+    assert_eq!(histogram.first_key_value(), Some((&5, &4)));
+}
+
+#[test]
+#[should_panic(expected = "expansion factor must be at least 1")]
+fn test_zero_expansion() {
+    sum_distances(&[(0, 0), (2, 2)], 0);
+}
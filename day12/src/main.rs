@@ -1,6 +1,10 @@
 use itertools::{repeat_n, join, Itertools};
+use num::{BigUint, One, Zero};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+#[cfg(test)]
 use std::iter::zip;
-use std::collections::HashMap;
 
 fn main() {
     let input = include_str!("../input.txt");
@@ -9,120 +13,436 @@ fn main() {
     println!("Part 1: {result1}");
     assert_eq!(result1, 7017);
 
-    let start_time = std::time::Instant::now();
-    let result2 = part2(input);
+    // `--threads N` sets the size of the thread pool for part 2 (and
+    // `--slowest`), and `--progress` shows how many lines are done.
+    let args: Vec<String> = std::env::args().collect();
+    let threads = args
+        .iter()
+        .position(|arg| arg == "--threads")
+        .and_then(|pos| args.get(pos + 1))
+        .map_or(0, |arg| arg.parse().expect("a number of threads"));
+    let progress = args.iter().any(|arg| arg == "--progress");
+
+    let start_time = Instant::now();
+    let result2 = if threads != 0 || progress {
+        solve_lines(input, 5, threads, progress).iter().map(|line| line.count).sum()
+    } else {
+        part2(input)
+    };
     let duration = start_time.elapsed().as_secs_f64();
+    if progress {
+        eprintln!();
+    }
     println!("Part 2: {result2} in {duration:.9} seconds");
     assert_eq!(result2, 527570479489);
+
+    // `--unfold N` shows the count for each line at ×1 and at ×N
+    if let Some(pos) = args.iter().position(|arg| arg == "--unfold") {
+        let factor = args.get(pos + 1).and_then(|arg| arg.parse().ok()).expect("an unfold factor");
+        let (total, lines) = unfolded_counts(input, factor);
+        for (line_number, (line, counts)) in input.lines().zip(lines).enumerate() {
+            println!("{:>4}: {line}  ×1: {}  ×{factor}: {}", line_number + 1, counts.folded, counts.unfolded);
+        }
+        println!("Total at ×{factor}: {total}");
+    }
+
+    // `--slowest K` solves part 2 again and reports the K lines that took
+    // the longest.
+    if let Some(pos) = args.iter().position(|arg| arg == "--slowest") {
+        let how_many = args.get(pos + 1).and_then(|arg| arg.parse().ok()).unwrap_or(10);
+
+        let start_time = Instant::now();
+        let timings = solve_lines(input, 5, threads, progress);
+        let elapsed = start_time.elapsed();
+        if progress {
+            eprintln!();
+        }
+        let total: u64 = timings.iter().map(|line| line.count).sum();
+        let busy: Duration = timings.iter().map(|line| line.duration).sum();
+        let pool_size = timings.first().map_or(0, |line| line.pool_size);
+        println!("Part 2: {total} in {elapsed:?} on {pool_size} threads ({busy:?} spent solving lines)");
+        let lines = input.lines().collect_vec();
+        for line in timings.iter().sorted_by_key(|line| std::cmp::Reverse(line.duration)).take(how_many) {
+            let share = 100.0 * line.duration.as_secs_f64() / busy.as_secs_f64();
+            println!(
+                "{:>4}: {:?} ({share:.1}%)  {}  {}",
+                line.line_number, line.duration, lines[line.line_number - 1], line.count
+            );
+        }
+    }
+
+    // `--show N` lists the arrangements of line N (the first few, anyway),
+    // and a few chosen at random
+    if let Some(pos) = args.iter().position(|arg| arg == "--show") {
+        let line_number: usize = args.get(pos + 1).and_then(|arg| arg.parse().ok()).expect("a line number");
+        let line = input.lines().nth(line_number - 1).expect("a valid line number");
+        let (pattern, groups) = parse_line(line);
+        let arrangements = Arrangements::new(pattern.as_bytes(), &groups);
+        println!("{line}: {} arrangements", arrangements.count());
+        for arrangement in arrangements.iter().take(20) {
+            println!("  {arrangement}");
+        }
+        println!("Random samples:");
+        let mut rng = SplitMix64::new(line_number as u64);
+        for _ in 0..5 {
+            println!("  {}", arrangements.sample(&mut rng).unwrap());
+        }
+    }
 }
 
-fn part1(input: &str) -> u32 {
+fn part1(input: &str) -> u64 {
     input
         .lines()
         .map(|line| {
-            let (pattern, groups) = line.split_once(' ').unwrap();
-            let groups: Vec<u32> = groups.split(',').map(|s| s.parse().unwrap()).collect();
-            let num_questions = pattern.chars().filter(|c| c == &'?').count() as u32;
-            let num_hashes = groups.iter().sum::<u32>() - pattern.chars().filter(|c| c==&'#').count() as u32;
-            let num_dots = num_questions - num_hashes;
-
-            count_valid_arrangements(pattern, num_hashes, num_dots, &groups)
+            let (pattern, groups) = parse_line(line);
+            count_arrangements(pattern.as_bytes(), &groups)
         })
         .sum()
 }
 
 fn part2(input: &str) -> u64 {
-    input
+    solve_lines(input, 5, 0, false)
+        .iter()
+        .map(|line| line.count)
+        .sum()
+}
+
+// The result of solving one line of input, and how long it took
+#[derive(Debug)]
+struct LineTiming {
+    line_number: usize,
+    count: u64,
+    duration: Duration,
+    // the size of the thread pool that solved it
+    pool_size: usize,
+}
+
+// Count the arrangements of every line, unfolded by `factor`.  Each line is
+// independent, so they are solved in parallel on a pool of `threads` threads
+// (0 means one per CPU).  The results are in line order, so summing them is
+// deterministic.  With `progress`, the number of lines solved so far is
+// written to stderr.
+fn solve_lines(input: &str, factor: usize, threads: usize, progress: bool) -> Vec<LineTiming> {
+    let lines = input.lines().collect_vec();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("able to create thread pool");
+    let done = AtomicUsize::new(0);
+
+    pool.install(|| {
+        lines
+            .par_iter()
+            .enumerate()
+            .map(|(index, line)| {
+                let start_time = Instant::now();
+                let (pattern, groups) = parse_line(line);
+                let (pattern, groups) = unfold(pattern, &groups, factor);
+                let count = count_arrangements(pattern.as_bytes(), &groups);
+                let duration = start_time.elapsed();
+
+                if progress {
+                    let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                    eprint!("\r{done}/{} lines", lines.len());
+                }
+                LineTiming { line_number: index + 1, count, duration, pool_size: rayon::current_num_threads() }
+            })
+            .collect()
+    })
+}
+
+// The pattern is repeated `factor` times, separated by '?', and the
+// groups are repeated `factor` times.
+fn unfold(pattern: &str, groups: &[usize], factor: usize) -> (String, Vec<usize>) {
+    assert!(factor > 0, "unfold factor must be at least 1");
+    let pattern = join(repeat_n(pattern, factor), "?");
+    let groups = repeat_n(groups, factor).flatten().copied().collect_vec();
+    (pattern, groups)
+}
+
+// The counts for one line of input, folded and unfolded
+#[derive(Debug, PartialEq, Eq)]
+struct LineCounts {
+    folded: BigUint,
+    unfolded: BigUint,
+}
+
+// Count the arrangements of every line unfolded by any factor.  Returns the
+// total and the counts for each line.  Counts are exact, even when they
+// would overflow a u64.
+fn unfolded_counts(input: &str, factor: usize) -> (BigUint, Vec<LineCounts>) {
+    let lines = input
         .lines()
-        .enumerate()
-        //.par_bridge()
-        .map(|(_line_number, line)| {
-            let (pattern, groups) = line.split_once(' ').unwrap();
-            let groups: Vec<u32> = groups.split(',').map(|s| s.parse().unwrap()).collect();
-            // Pattern is repeated 5 times, separated by '?'
-            let pattern = join(repeat_n(pattern, 5), "?");
-            // Groups is repeated 5 times
-            let groups = repeat_n(groups, 5).flatten().collect_vec();
-
-            let num_questions = pattern.chars().filter(|c| c == &'?').count() as u32;
-            let num_hashes = groups.iter().sum::<u32>() - pattern.chars().filter(|c| c==&'#').count() as u32;
-            let num_dots = num_questions - num_hashes;
-
-            //let result = count_valid_arrangements(&pattern, num_hashes, num_dots, &groups);
-            let mut cache = HashMap::new();
-            let result = count_matches(pattern.as_bytes(), &groups, 0, 0, num_dots, &mut cache);
-
-            result
+        .map(|line| {
+            let (pattern, groups) = parse_line(line);
+            let folded = count_arrangements_exact(pattern.as_bytes(), &groups);
+            let (pattern, groups) = unfold(pattern, &groups, factor);
+            let unfolded = count_arrangements_exact(pattern.as_bytes(), &groups);
+            LineCounts { folded, unfolded }
         })
-        .sum()
+        .collect_vec();
+    let total = lines.iter().map(|counts| &counts.unfolded).sum();
+    (total, lines)
+}
+
+fn parse_line(line: &str) -> (&str, Vec<usize>) {
+    let (pattern, groups) = line.split_once(' ').unwrap();
+    let groups = groups.split(',').map(|s| s.parse().unwrap()).collect();
+    (pattern, groups)
 }
 
-fn count_matches(
-    pattern: &[u8], groups: &[u32],
-    pattern_offset: usize, group_offset: usize,
-    num_dots: u32,
-    cache: &mut HashMap<(usize, usize), u64>
-) -> u64 {
-    if let Some(result) = cache.get(&(pattern_offset, group_offset)) {
-        return *result
+//
+// Dynamic programming, filled in from the end of the pattern.
+// ways[i][g] is the number of ways that pattern[i..] can hold groups[g..].
+// At position i, we can either:
+//  * Make pattern[i] a dot (unless it is a hash), leaving ways[i+1][g]
+//  * Start group g at position i.  Rather than extending the run of hashes
+//    one position at a time, place the whole run at once: the next
+//    groups[g] positions must not contain a dot, and the position after
+//    them (if any) must not be a hash, since it separates this group from
+//    the next.  That leaves ways[i+groups[g]+1][g+1].
+// With a prefix sum of dots, "no dot in this window" is a subtraction, so
+// the whole table takes O(len × groups) time.
+//
+fn count_arrangements(pattern: &[u8], groups: &[usize]) -> u64 {
+    count_arrangements_with(pattern, groups, |a: &u64, b: &u64| a.checked_add(*b))
+        .expect("arrangement count overflowed a u64")
+}
+
+// Like count_arrangements, but switches to BigUint if a u64 would overflow
+fn count_arrangements_exact(pattern: &[u8], groups: &[usize]) -> BigUint {
+    match count_arrangements_with(pattern, groups, |a: &u64, b: &u64| a.checked_add(*b)) {
+        Some(count) => BigUint::from(count),
+        None => count_arrangements_with(pattern, groups, |a: &BigUint, b: &BigUint| Some(a + b)).unwrap(),
+    }
+}
+
+fn count_arrangements_with<T: Clone + Zero + One>(
+    pattern: &[u8], groups: &[usize],
+    add: impl Fn(&T, &T) -> Option<T>
+) -> Option<T> {
+    Springs::new(pattern, groups)
+        .table(add)
+        .map(|ways| ways[0][0].clone())
+}
+
+// A pattern and its groups, with the prefix sum of dots that lets us
+// quickly test whether a group fits at a given position.
+struct Springs {
+    pattern: Vec<u8>,
+    groups: Vec<usize>,
+    // dots_before[i] is the number of dots in pattern[..i]
+    dots_before: Vec<usize>,
+}
+
+impl Springs {
+    fn new(pattern: &[u8], groups: &[usize]) -> Self {
+        let mut dots_before = vec![0; pattern.len() + 1];
+        for (i, &c) in pattern.iter().enumerate() {
+            assert!(b".#?".contains(&c), "invalid character in pattern");
+            dots_before[i + 1] = dots_before[i] + usize::from(c == b'.');
+        }
+        Springs { pattern: pattern.to_vec(), groups: groups.to_vec(), dots_before }
+    }
+
+    // Where we continue from if pattern[i] is a dot
+    fn dot_next(&self, i: usize) -> Option<usize> {
+        (self.pattern[i] != b'#').then_some(i + 1)
     }
-    
-    let orig_pattern_offset = pattern_offset;
-    let mut pattern_offset = pattern_offset;
-    let mut num_dots = num_dots;
-    let mut result = 0;
 
-    assert!(pattern_offset <= pattern.len());
-    assert!(group_offset <= groups.len());
-
-    // If we have used up all the groups, then the remainder of the pattern must
-    // not contain any hashes.
-    if group_offset == groups.len() {
-        for c in &pattern[pattern_offset..] {
-            if *c as char == '#' {
-                // Impossible match
-                cache.insert((orig_pattern_offset, group_offset), 0);
-                return 0;
+    // Where we continue from if group g starts at pattern[i], including
+    // the dot that follows it
+    fn group_next(&self, i: usize, g: usize) -> Option<usize> {
+        let len = self.pattern.len();
+        let end = i + self.groups.get(g)?;
+        let no_dot = end <= len && self.dots_before[end] == self.dots_before[i];
+        (no_dot && (end == len || self.pattern[end] != b'#')).then_some((end + 1).min(len))
+    }
+
+    // The table of ways[i][g], or None if `add` overflowed
+    fn table<T: Clone + Zero + One>(&self, add: impl Fn(&T, &T) -> Option<T>) -> Option<Vec<Vec<T>>> {
+        let len = self.pattern.len();
+        let num_groups = self.groups.len();
+        let mut ways = vec![vec![T::zero(); num_groups + 1]; len + 1];
+        // An empty pattern can only hold no groups
+        ways[len][num_groups] = T::one();
+        for i in (0..len).rev() {
+            for g in 0..=num_groups {
+                let mut count = T::zero();
+                if let Some(next) = self.dot_next(i) {
+                    count = add(&count, &ways[next][g])?;
+                }
+                if let Some(next) = self.group_next(i, g) {
+                    count = add(&count, &ways[next][g + 1])?;
+                }
+                ways[i][g] = count;
             }
         }
-        // It was all dots or question marks, or empty
-        cache.insert((orig_pattern_offset, group_offset), 1);
-        return 1;
+        Some(ways)
+    }
+}
+
+// Every concrete arrangement of a pattern and its groups, in lexicographic
+// order ('#' sorts before '.').  The counts in the DP table tell us which
+// choices lead to at least one arrangement, so we never explore a dead end,
+// and how many arrangements follow each choice, so we can jump straight to
+// the n'th one.  Nothing is generated until it is asked for.
+struct Arrangements {
+    springs: Springs,
+    ways: Vec<Vec<BigUint>>,
+}
+
+// What a step writes: nothing, a '.', or a group of '#' plus its separator
+#[derive(Clone, Copy)]
+enum Piece {
+    Start,
+    Dot,
+    Group,
+}
+
+impl Arrangements {
+    fn new(pattern: &[u8], groups: &[usize]) -> Self {
+        let springs = Springs::new(pattern, groups);
+        let ways = springs.table(|a: &BigUint, b: &BigUint| Some(a + b)).unwrap();
+        Arrangements { springs, ways }
     }
 
-    // Skip over leading dots in the remaining pattern
-    while pattern_offset < pattern.len() && pattern[pattern_offset] as char  == '.' {
-        pattern_offset += 1;
+    fn count(&self) -> &BigUint {
+        &self.ways[0][0]
     }
-    assert!(pattern_offset < pattern.len());
 
-    if num_dots > 0 && pattern[pattern_offset] as char == '?' {
-        // Try using the question mark as a dot
-        result += count_matches(pattern, groups, pattern_offset+1, group_offset, num_dots-1, cache);
+    fn iter(&self) -> ArrangementIter<'_> {
+        let stack = if self.count().is_zero() { vec![] } else { vec![(0, 0, 0, Piece::Start)] };
+        ArrangementIter { arrangements: self, stack, buffer: String::new() }
     }
 
-    // Try to match the next group at the start of the remaining pattern.
-    // The next group_len bytes of the pattern must not contain a dot.
-    // If there is more pattern after that, it must not be a hash.
-    let group_len = groups[group_offset] as usize;
-    assert!(pattern.len() - pattern_offset >= group_len);
-    if pattern[pattern_offset..pattern_offset+group_len].iter().all(|c| *c as char != '.') {
-        pattern_offset += group_len;
-        if pattern_offset == pattern.len() || pattern[pattern_offset] as char == '.' || (num_dots > 0 && pattern[pattern_offset] as char != '#') {
-            if pattern_offset < pattern.len() {
-                if pattern[pattern_offset] as char == '?' {
-                    num_dots -= 1;
+    // Write the piece at position i, returning the new (i, g)
+    fn write_piece(&self, buffer: &mut String, i: usize, g: usize, piece: Piece) -> (usize, usize) {
+        match piece {
+            Piece::Start => (i, g),
+            Piece::Dot => {
+                buffer.push('.');
+                (i + 1, g)
+            }
+            Piece::Group => {
+                let next = self.springs.group_next(i, g).unwrap();
+                let group_len = self.springs.groups[g];
+                buffer.extend(repeat_n('#', group_len));
+                if next > i + group_len {
+                    buffer.push('.');
                 }
-                pattern_offset += 1;
+                (next, g + 1)
             }
-            result += count_matches(pattern, groups, pattern_offset, group_offset+1, num_dots, cache);
         }
     }
 
-    cache.insert((orig_pattern_offset, group_offset), result);
-    result
+    // The choices at (i, g) that lead to at least one arrangement, along with
+    // how many arrangements follow each, in lexicographic order
+    fn choices(&self, i: usize, g: usize) -> impl Iterator<Item = (Piece, &BigUint)> {
+        let group = self.springs.group_next(i, g).map(|next| (Piece::Group, &self.ways[next][g + 1]));
+        let dot = self.springs.dot_next(i).map(|next| (Piece::Dot, &self.ways[next][g]));
+        group.into_iter().chain(dot).filter(|(_, count)| !count.is_zero())
+    }
+
+    // The n'th arrangement (counting from 0) in lexicographic order
+    fn nth(&self, n: &BigUint) -> Option<String> {
+        if n >= self.count() {
+            return None;
+        }
+        let mut n = n.clone();
+        let mut buffer = String::new();
+        let (mut i, mut g) = (0, 0);
+        while i < self.springs.pattern.len() {
+            let mut chosen = None;
+            for (piece, count) in self.choices(i, g) {
+                if &n < count {
+                    chosen = Some(piece);
+                    break;
+                }
+                n -= count;
+            }
+            (i, g) = self.write_piece(&mut buffer, i, g, chosen.unwrap());
+        }
+        Some(buffer)
+    }
+
+    // An arrangement chosen uniformly at random
+    fn sample(&self, rng: &mut SplitMix64) -> Option<String> {
+        if self.count().is_zero() {
+            return None;
+        }
+        self.nth(&rng.below(self.count()))
+    }
+}
+
+struct ArrangementIter<'a> {
+    arrangements: &'a Arrangements,
+    // Depth first search: choices still to try.  Each one is a piece to
+    // write at pattern[i], having already matched groups[..g], on top of
+    // the first `prefix_len` characters: (prefix_len, i, g, piece).
+    stack: Vec<(usize, usize, usize, Piece)>,
+    buffer: String,
+}
+
+impl Iterator for ArrangementIter<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while let Some((prefix_len, i, g, piece)) = self.stack.pop() {
+            self.buffer.truncate(prefix_len);
+            let (i, g) = self.arrangements.write_piece(&mut self.buffer, i, g, piece);
+            if i == self.arrangements.springs.pattern.len() {
+                return Some(self.buffer.clone());
+            }
+            // Push in reverse, so the lexicographically first choice is popped next
+            let choices = self.arrangements.choices(i, g).collect_vec();
+            for (piece, _) in choices.into_iter().rev() {
+                self.stack.push((self.buffer.len(), i, g, piece));
+            }
+        }
+        None
+    }
+}
+
+// A small, seedable pseudo-random number generator (SplitMix64), so we
+// don't need to pull in a crate just for sampling.
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // A uniformly random number in 0..bound, by rejection sampling
+    fn below(&mut self, bound: &BigUint) -> BigUint {
+        assert!(!bound.is_zero());
+        let bits = bound.bits();
+        loop {
+            let digits = (0..bits.div_ceil(64)).map(|_| self.next_u64()).collect_vec();
+            let candidate = BigUint::from_slice(
+                &digits.iter().flat_map(|&d| [d as u32, (d >> 32) as u32]).collect_vec()
+            ) >> (digits.len() as u64 * 64 - bits);
+            if &candidate < bound {
+                return candidate;
+            }
+        }
+    }
 }
 
+// The original brute force solution: try every way of replacing the
+// question marks.  It is far too slow for part 2, but is a useful
+// reference for testing.
+#[cfg(test)]
 fn count_valid_arrangements(pattern: &str, num_hashes: u32, num_dots: u32, groups: &[u32]) -> u32 {
     // Try replacing the first question mark with a hash or dot, and compute
     // the number of valid arrangements for each.
@@ -159,6 +479,7 @@ fn count_valid_arrangements(pattern: &str, num_hashes: u32, num_dots: u32, group
     result
 }
 
+#[cfg(test)]
 fn initial_groups(pattern: &str) -> Vec<u32> {
     let mut result = vec![];
 
@@ -207,6 +528,138 @@ static EXAMPLE1: &str = "\
 ?###???????? 3,2,1
 ";
 
+#[test]
+fn test_matches_brute_force() {
+    for line in EXAMPLE1.lines().chain(include_str!("../input.txt").lines().take(20)) {
+        let (pattern, groups) = parse_line(line);
+        let num_questions = pattern.chars().filter(|c| c == &'?').count() as u32;
+        let num_hashes = groups.iter().sum::<usize>() as u32 - pattern.chars().filter(|c| c==&'#').count() as u32;
+        let num_dots = num_questions - num_hashes;
+        let groups_u32 = groups.iter().map(|&g| g as u32).collect_vec();
+        assert_eq!(
+            count_arrangements(pattern.as_bytes(), &groups) as u32,
+            count_valid_arrangements(pattern, num_hashes, num_dots, &groups_u32),
+            "{line}"
+        );
+    }
+}
+
+#[test]
+fn test_count_arrangements_edges() {
+    assert_eq!(count_arrangements(b"", &[]), 1);
+    assert_eq!(count_arrangements(b"???", &[]), 1);
+    assert_eq!(count_arrangements(b"#", &[]), 0);
+    assert_eq!(count_arrangements(b"", &[1]), 0);
+    assert_eq!(count_arrangements(b"###", &[3]), 1);
+    assert_eq!(count_arrangements(b"###", &[2]), 0);
+    assert_eq!(count_arrangements(b"????", &[1, 1]), 3);
+}
+
+#[test]
+fn test_unfolded_counts() {
+    let (total, lines) = unfolded_counts(EXAMPLE1, 5);
+    assert_eq!(total, BigUint::from(525152u32));
+    assert_eq!(lines[1], LineCounts { folded: 4u32.into(), unfolded: 16384u32.into() });
+    assert_eq!(lines[3], LineCounts { folded: 1u32.into(), unfolded: 16u32.into() });
+    assert_eq!(lines[5], LineCounts { folded: 10u32.into(), unfolded: 506250u32.into() });
+
+    let (total, _) = unfolded_counts(EXAMPLE1, 1);
+    assert_eq!(total, BigUint::from(21u32));
+}
+
+#[test]
+fn test_unfold_overflow() {
+    // ".??..??...?##. 1,1,3" has 4 arrangements, and each extra copy can
+    // only multiply that by 8 (the joining '?' is always a dot), giving
+    // 4 * 8^(N-1).  At ×50 that is 2^149, far too big for a u64.
+    let (total, lines) = unfolded_counts(".??..??...?##. 1,1,3", 50);
+    assert_eq!(lines[0].folded, BigUint::from(4u32));
+    assert_eq!(total, BigUint::from(2u32).pow(149));
+    let (pattern, groups) = unfold(".??..??...?##.", &[1, 1, 3], 50);
+    assert!(count_arrangements_with(pattern.as_bytes(), &groups, |a: &u64, b: &u64| a.checked_add(*b)).is_none());
+}
+
+#[cfg(test)]
+fn matches(arrangement: &str, pattern: &str, groups: &[usize]) -> bool {
+    arrangement.len() == pattern.len()
+        && zip(arrangement.chars(), pattern.chars()).all(|(a, p)| p == '?' || a == p)
+        && arrangement.split('.').filter(|s| !s.is_empty()).map(str::len).collect_vec() == groups
+}
+
+#[test]
+fn test_enumerate_arrangements() {
+    for line in EXAMPLE1.lines() {
+        let (pattern, groups) = parse_line(line);
+        let arrangements = Arrangements::new(pattern.as_bytes(), &groups);
+        let all = arrangements.iter().collect_vec();
+        assert_eq!(BigUint::from(all.len()), *arrangements.count(), "{line}");
+        assert!(all.iter().all(|a| matches(a, pattern, &groups)), "{line}");
+        // Strictly increasing means sorted and unique
+        assert!(all.windows(2).all(|w| w[0] < w[1]), "{line}");
+        for (n, arrangement) in all.iter().enumerate() {
+            assert_eq!(arrangements.nth(&BigUint::from(n)).as_ref(), Some(arrangement));
+        }
+        assert_eq!(arrangements.nth(arrangements.count()), None);
+    }
+
+    let arrangements = Arrangements::new(b"?###????????", &[3, 2, 1]);
+    assert_eq!(arrangements.iter().take(3).collect_vec(), vec![
+        ".###.##.#...",
+        ".###.##..#..",
+        ".###.##...#.",
+    ]);
+}
+
+#[test]
+fn test_no_arrangements() {
+    let arrangements = Arrangements::new(b"#.#", &[3]);
+    assert_eq!(arrangements.iter().next(), None);
+    assert_eq!(arrangements.sample(&mut SplitMix64::new(1)), None);
+    assert_eq!(Arrangements::new(b"", &[]).iter().collect_vec(), vec![String::new()]);
+}
+
+#[test]
+fn test_huge_arrangement_space() {
+    // Far more arrangements than fit in a u64, but the first few are cheap
+    let (pattern, groups) = unfold("?.??.??.???", &[1, 1, 2], 50);
+    let arrangements = Arrangements::new(pattern.as_bytes(), &groups);
+    assert!(arrangements.count().bits() > 128);
+    let first = arrangements.iter().take(3).collect_vec();
+    assert!(first.iter().all(|a| matches(a, &pattern, &groups)));
+    let sample = arrangements.sample(&mut SplitMix64::new(42)).unwrap();
+    assert!(matches(&sample, &pattern, &groups));
+}
+
+#[test]
+fn test_sample_is_uniform() {
+    // 4 arrangements, so each should come up about 1000 times in 4000
+    let arrangements = Arrangements::new(b".??..??...?##.", &[1, 1, 3]);
+    let mut rng = SplitMix64::new(2023);
+    let counts = (0..4000).map(|_| arrangements.sample(&mut rng).unwrap()).counts();
+    assert_eq!(counts.len(), 4);
+    assert!(counts.values().all(|&n| (850..1150).contains(&n)), "{counts:?}");
+}
+
+#[test]
+fn test_solve_lines_deterministic() {
+    let expected = [1, 16384, 1, 16, 2500, 506250];
+    for threads in [1, 2, 4] {
+        let timings = solve_lines(EXAMPLE1, 5, threads, false);
+        assert_eq!(timings.iter().map(|line| line.line_number).collect_vec(), (1..=6).collect_vec());
+        assert_eq!(timings.iter().map(|line| line.count).collect_vec(), expected);
+    }
+}
+
+#[test]
+fn test_part2_threads() {
+    // With `--threads`, part 2 solves its lines on a pool of exactly that size.
+    for threads in [1, 3] {
+        let timings = solve_lines(EXAMPLE1, 5, threads, false);
+        assert!(timings.iter().all(|line| line.pool_size == threads));
+        assert_eq!(timings.iter().map(|line| line.count).sum::<u64>(), 525152);
+    }
+}
+
 #[test]
 fn test_part1() {
     assert_eq!(part1(EXAMPLE1), 21);
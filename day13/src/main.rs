@@ -1,110 +1,234 @@
 fn main() {
     let input = include_str!("../input.txt");
 
-    let result1 = part1(input);
+    let result1 = part1(input).expect("every pattern has a reflection");
     println!("Part 1: {result1}");
     assert_eq!(result1, 30487);
 
-    let result2 = part2(input);
+    let result2 = part2(input).expect("every pattern has a smudged reflection");
     println!("Part 2: {result2}");
     assert_eq!(result2, 31954);
-}
-
-fn part1(input: &str) -> usize {
-    let mut result = 0;
-    'outer: for pattern in input.split("\n\n") {
-        let pattern: Vec<Vec<char>> = pattern.lines().map(|line| line.chars().collect()).collect();
-
-        // See if there is a reflection about a row.  Here, `row` is the
-        // number of rows before the reflection
-        for row in 1..pattern.len() {
-            // How many rows fit on both sides of `row`?
-            let height = row.min(pattern.len() - row);
-            if pattern[(row - height)..row]
-                .iter()
-                .eq(pattern[row..(row + height)].iter().rev())
-            {
-                result += 100 * row;
-                continue 'outer;
-            }
-        }
-
-        // See if there is a reflection about a column.  Here, `col` is
-        // the number of columns to the left of the reflection.
-        let total_columns = pattern[0].len();
-        for col in 1..total_columns {
-            // How many columns fit on both sides of `col`?
-            let width = col.min(total_columns - col);
-            if pattern.iter().all(|row| {
-                row[(col - width)..col]
-                    .iter()
-                    .eq(row[col..(col + width)].iter().rev())
-            }) {
-                result += col;
-                continue 'outer;
-            }
-        }
 
-        panic!("No reflection found!");
+    // With `--report`, list every mirror line in every pattern, both
+    // perfect and with a single smudge.
+    if std::env::args().any(|arg| arg == "--report") {
+        println!();
+        print!("{}", report(input));
     }
-    result
+}
+
+fn part1(input: &str) -> Result<usize, NoReflection> {
+    summarize(input, 0)
 }
 
 //
 // Part 2 is the same as part 1, except that the reflection will have
 // exactly one difference.
 //
-fn part2(input: &str) -> usize {
-    let mut result = 0;
-    'outer: for pattern in input.split("\n\n") {
-        let pattern: Vec<Vec<char>> = pattern.lines().map(|line| line.chars().collect()).collect();
-
-        // See if there is a reflection about a row.  Here, `row` is the
-        // number of rows before the reflection
-        for row in 1..pattern.len() {
-            // How many rows fit on both sides of `row`?
-            let height = row.min(pattern.len() - row);
-            let differences: usize = pattern[(row - height)..row]
-                .iter()
-                .zip(pattern[row..(row + height)].iter().rev())
-                .map(|(row1, row2)| {
-                    row1.iter()
-                        .zip(row2.iter())
-                        .filter(|(c1, c2)| c1 != c2)
-                        .count()
-                })
-                .sum();
-            if differences == 1 {
-                result += 100 * row;
-                continue 'outer;
-            }
+fn part2(input: &str) -> Result<usize, NoReflection> {
+    summarize(input, 1)
+}
+
+fn summarize(input: &str, smudges: usize) -> Result<usize, NoReflection> {
+    input
+        .split("\n\n")
+        .enumerate()
+        .map(|(index, pattern)| {
+            let pattern = Pattern::parse(pattern);
+            find_reflection(&pattern, smudges)
+                .map(|reflection| reflection.summary())
+                .ok_or(NoReflection { pattern: index + 1, smudges })
+        })
+        .sum()
+}
+
+// Pattern number `pattern` (1-based) has no mirror line with exactly
+// `smudges` differences.
+#[derive(Debug, PartialEq, Eq)]
+struct NoReflection {
+    pattern: usize,
+    smudges: usize,
+}
+
+impl std::fmt::Display for NoReflection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no reflection with {} smudge(s) in pattern {}", self.smudges, self.pattern)
+    }
+}
+
+impl std::error::Error for NoReflection {}
+
+// Every mirror line (perfect or smudged) in every pattern, one per line.
+fn report(input: &str) -> String {
+    let mut result = String::new();
+    for (index, pattern) in input.split("\n\n").enumerate() {
+        let pattern = Pattern::parse(pattern);
+        let mirrors = [0, 1].iter().flat_map(|&smudges| reflections(&pattern, smudges));
+        let mut found = false;
+        for mirror in mirrors {
+            found = true;
+            result.push_str(&format!("Pattern {}: {mirror}\n", index + 1));
+        }
+        if !found {
+            result.push_str(&format!("Pattern {}: no reflection\n", index + 1));
         }
+    }
+    result
+}
+
+// A pattern stored twice: once as a bitmask per row (bit `c` set if
+// column `c` is a rock), and once as a bitmask per column (bit `r` set
+// if row `r` is a rock).  The number of cells that differ between two
+// rows (or two columns) is then the popcount of their XOR.
+#[derive(Debug)]
+struct Pattern {
+    rows: Vec<u64>,
+    cols: Vec<u64>,
+}
+
+impl Pattern {
+    fn parse(s: &str) -> Self {
+        let lines: Vec<&[u8]> = s.lines().map(str::as_bytes).collect();
+        let height = lines.len();
+        let width = lines[0].len();
+        assert!(width <= 64 && height <= 64, "pattern too large");
 
-        // See if there is a reflection about a column.  Here, `col` is
-        // the number of columns to the left of the reflection.
-        let total_columns = pattern[0].len();
-        for col in 1..total_columns {
-            // How many columns fit on both sides of `col`?
-            let width = col.min(total_columns - col);
-            let differences: usize = pattern
-                .iter()
-                .map(|row| {
-                    row[(col - width)..col]
-                        .iter()
-                        .zip(row[col..(col + width)].iter().rev())
-                        .filter(|(c1,c2)| c1 != c2)
-                        .count()
-                })
-                .sum();
-            if differences == 1 {
-                result += col;
-                continue 'outer;
+        let mut rows = vec![0u64; height];
+        let mut cols = vec![0u64; width];
+        for (r, line) in lines.iter().enumerate() {
+            assert_eq!(line.len(), width, "ragged pattern");
+            for (c, &b) in line.iter().enumerate() {
+                if b == b'#' {
+                    rows[r] |= 1 << c;
+                    cols[c] |= 1 << r;
+                }
             }
         }
+        Pattern { rows, cols }
+    }
+}
 
-        panic!("No reflection found!");
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reflection {
+    // The number of rows above a horizontal mirror line
+    Horizontal(usize),
+    // The number of columns to the left of a vertical mirror line
+    Vertical(usize),
+}
+
+impl Reflection {
+    fn summary(&self) -> usize {
+        match *self {
+            Reflection::Horizontal(rows) => 100 * rows,
+            Reflection::Vertical(cols) => cols,
+        }
+    }
+}
+
+// A mirror line, how many rows (or columns) it reflects on each side,
+// and the cells that must be flipped to make the reflection perfect.
+#[derive(Debug, PartialEq, Eq)]
+struct Mirror {
+    reflection: Reflection,
+    extent: usize,
+    smudges: Vec<Smudge>,
+}
+
+// A cell that is the wrong way round; `fixed` is what it should be
+// flipped to.  Flipping either of the two mismatched cells would do, and
+// this is always the one above (or left of) the mirror line, so it may
+// not be the cell the puzzle text chooses.
+#[derive(Debug, PartialEq, Eq)]
+struct Smudge {
+    row: usize,
+    col: usize,
+    fixed: char,
+}
+
+impl std::fmt::Display for Mirror {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (kind, before, unit) = match self.reflection {
+            Reflection::Horizontal(rows) => ("horizontal", rows, "rows"),
+            Reflection::Vertical(cols) => ("vertical", cols, "columns"),
+        };
+        write!(f, "{kind} mirror after {before} {unit}, {} {unit} reflected", self.extent)?;
+        for smudge in &self.smudges {
+            write!(f, ", smudge at ({}, {}) → {}", smudge.row, smudge.col, smudge.fixed)?;
+        }
+        Ok(())
     }
-    result
+}
+
+// Find a mirror line where exactly `smudges` cells would need to be
+// flipped for the reflection to be perfect.  Lines between rows are
+// tried before lines between columns.
+fn find_reflection(pattern: &Pattern, smudges: usize) -> Option<Reflection> {
+    find_mirrors(&pattern.rows, smudges)
+        .next()
+        .map(Reflection::Horizontal)
+        .or_else(|| find_mirrors(&pattern.cols, smudges).next().map(Reflection::Vertical))
+}
+
+// Every mirror line with exactly `smudges` differences, horizontal ones
+// first, each in order from the top (or left).  Rows and columns in
+// smudge locations are 0-based, like the mirror positions count lines.
+fn reflections(pattern: &Pattern, smudges: usize) -> Vec<Mirror> {
+    let horizontal = find_mirrors(&pattern.rows, smudges).map(|mirror| Mirror {
+        reflection: Reflection::Horizontal(mirror),
+        extent: extent(&pattern.rows, mirror),
+        smudges: mismatches(&pattern.rows, mirror)
+            .map(|(row, col, rock)| Smudge { row, col, fixed: flipped(rock) })
+            .collect(),
+    });
+    let vertical = find_mirrors(&pattern.cols, smudges).map(|mirror| Mirror {
+        reflection: Reflection::Vertical(mirror),
+        extent: extent(&pattern.cols, mirror),
+        smudges: mismatches(&pattern.cols, mirror)
+            .map(|(col, row, rock)| Smudge { row, col, fixed: flipped(rock) })
+            .collect(),
+    });
+    horizontal.chain(vertical).collect()
+}
+
+// The character a smudged cell should become, given whether it is
+// currently a rock.
+fn flipped(rock: bool) -> char {
+    if rock { '.' } else { '#' }
+}
+
+// The number of lines before each mirror whose reflected lines differ
+// in exactly `smudges` bits.
+fn find_mirrors(lines: &[u64], smudges: usize) -> impl Iterator<Item = usize> + '_ {
+    (1..lines.len()).filter(move |&mirror| differences(lines, mirror, smudges) == Some(smudges))
+}
+
+// How many lines fit on both sides of the mirror after `mirror` lines?
+fn extent(lines: &[u64], mirror: usize) -> usize {
+    mirror.min(lines.len() - mirror)
+}
+
+// The (line, bit, is a rock) of every mismatched cell before the mirror.
+fn mismatches(lines: &[u64], mirror: usize) -> impl Iterator<Item = (usize, usize, bool)> + '_ {
+    (mirror - extent(lines, mirror)..mirror).flat_map(move |line| {
+        let opposite = 2 * mirror - 1 - line;
+        let diff = lines[line] ^ lines[opposite];
+        (0..64)
+            .filter(move |bit| diff & (1 << bit) != 0)
+            .map(move |bit| (line, bit, lines[line] & (1 << bit) != 0))
+    })
+}
+
+// Count the bits that differ across the mirror placed after `mirror`
+// lines, giving up as soon as there are more than `limit`.
+fn differences(lines: &[u64], mirror: usize, limit: usize) -> Option<usize> {
+    let mut total = 0;
+    for (a, b) in lines[..mirror].iter().rev().zip(&lines[mirror..]) {
+        total += (a ^ b).count_ones() as usize;
+        if total > limit {
+            return None;
+        }
+    }
+    Some(total)
 }
 
 #[cfg(test)]
@@ -128,10 +252,83 @@ static EXAMPLE1: &str = "\
 
 #[test]
 fn test_part1() {
-    assert_eq!(part1(EXAMPLE1), 405);
+    assert_eq!(part1(EXAMPLE1), Ok(405));
 }
 
 #[test]
 fn test_part2() {
-    assert_eq!(part2(EXAMPLE1), 400);
+    assert_eq!(part2(EXAMPLE1), Ok(400));
+}
+
+#[test]
+fn test_find_reflection() {
+    let patterns: Vec<Pattern> = EXAMPLE1.split("\n\n").map(Pattern::parse).collect();
+    assert_eq!(find_reflection(&patterns[0], 0), Some(Reflection::Vertical(5)));
+    assert_eq!(find_reflection(&patterns[1], 0), Some(Reflection::Horizontal(4)));
+    assert_eq!(find_reflection(&patterns[0], 1), Some(Reflection::Horizontal(3)));
+    assert_eq!(find_reflection(&patterns[1], 1), Some(Reflection::Horizontal(1)));
+}
+
+#[test]
+fn test_more_smudges() {
+    // No perfect reflection.  Flipping one cell mirrors the last two
+    // rows; flipping two mirrors the first two rows.
+    let pattern = Pattern::parse("\
+#..
+.#.
+##.
+");
+    assert_eq!(find_reflection(&pattern, 0), None);
+    assert_eq!(find_reflection(&pattern, 1), Some(Reflection::Horizontal(2)));
+    assert_eq!(find_reflection(&pattern, 2), Some(Reflection::Horizontal(1)));
+}
+
+#[test]
+fn test_reflections() {
+    let patterns: Vec<Pattern> = EXAMPLE1.split("\n\n").map(Pattern::parse).collect();
+    assert_eq!(reflections(&patterns[0], 0), vec![Mirror {
+        reflection: Reflection::Vertical(5),
+        extent: 4,
+        smudges: vec![],
+    }]);
+    assert_eq!(reflections(&patterns[0], 1), vec![Mirror {
+        reflection: Reflection::Horizontal(3),
+        extent: 3,
+        smudges: vec![Smudge { row: 0, col: 0, fixed: '.' }],
+    }]);
+    // The puzzle fixes the second pattern at (1, 4); flipping its mirror
+    // image at (0, 4) works just as well.
+    assert_eq!(reflections(&patterns[1], 1), vec![Mirror {
+        reflection: Reflection::Horizontal(1),
+        extent: 1,
+        smudges: vec![Smudge { row: 0, col: 4, fixed: '.' }],
+    }]);
+}
+
+#[test]
+fn test_all_reflections() {
+    // Symmetric both ways, about two different row lines.
+    let pattern = Pattern::parse("\
+#..#
+#..#
+#..#
+#..#
+");
+    let mirrors: Vec<Reflection> = reflections(&pattern, 0).iter().map(|m| m.reflection).collect();
+    assert_eq!(mirrors, vec![
+        Reflection::Horizontal(1),
+        Reflection::Horizontal(2),
+        Reflection::Horizontal(3),
+        Reflection::Vertical(2),
+    ]);
+}
+
+#[test]
+fn test_no_reflection() {
+    let input = "\
+#.
+.#
+";
+    assert_eq!(part1(input), Err(NoReflection { pattern: 1, smudges: 0 }));
+    assert_eq!(report(input), "Pattern 1: no reflection\n");
 }
@@ -10,8 +10,51 @@ fn main() {
     let result2 = part2(input);
     println!("Part 2: {result2}");
     assert_eq!(result2, 96317);
+
+    // With `--spin PROGRAM TARGET`, run some other tilt program, such as
+    // "NNESW", for TARGET spins.
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--spin") {
+        let program = args.get(i + 1).expect("--spin needs a program");
+        let program = parse_program(program)
+            .unwrap_or_else(|c| panic!("invalid tilt {c:?} in program"));
+        let target = args.get(i + 2).map_or(1_000_000_000, |s| s.parse().expect("target count"));
+        let report = spin(input, &program, target);
+        println!();
+        match report.cycle {
+            Some((start, length)) => println!("Cycle starts after {start} spins, length {length}"),
+            None => println!("No cycle before {target} spins"),
+        }
+        println!("Load after {target} spins: {}", report.load);
+    }
+
+    // With `--animate [SPINS]`, redraw the grid after every tilt.  With
+    // `--csv FILE [SPINS]`, write the load after each spin to FILE.  Both
+    // run "NWSE" unless given `--program PROGRAM`, for 20 or 200 spins.
+    let program = match args.iter().position(|arg| arg == "--program") {
+        Some(i) => args.get(i + 1).expect("--program needs a program"),
+        None => "NWSE",
+    };
+    let program = parse_program(program).unwrap_or_else(|c| panic!("invalid tilt {c:?} in program"));
+    if let Some(i) = args.iter().position(|arg| arg == "--animate") {
+        let spins = args.get(i + 1).map_or(20, |s| s.parse().expect("number of spins"));
+        spin_trace(input, &program, spins, |puzzle, spin, tilt| {
+            print!("{CLEAR_SCREEN}{puzzle}");
+            println!("Spin {spin}, after tilting {tilt:?}: load {}", puzzle.total_load());
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        });
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--csv") {
+        let path = args.get(i + 1).expect("--csv needs a file name");
+        let spins = args.get(i + 2).map_or(200, |s| s.parse().expect("number of spins"));
+        let records = spin_trace(input, &program, spins, |_, _, _| {});
+        std::fs::write(path, to_csv(&records)).expect("write CSV file");
+    }
 }
 
+// Move the cursor to the top left, and clear the screen.
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
 fn part1(input: &str) -> usize {
     let mut puzzle = Puzzle::from_str(input);
     puzzle.tilt_north();
@@ -24,138 +67,314 @@ fn part1(input: &str) -> usize {
 // locations of all 'O' characters, or just the load value.  I'm going
 // to guess and say the load value is sufficient.
 //
-// It turns out that load value alone is insufficient.  I guess I'll
-// go with the raw state as a big String.
+// It turns out that load value alone is insufficient.  The state is
+// the positions of the round rocks, which is just the bitboard.
 //
 fn part2(input: &str) -> usize {
+    let program = parse_program("NWSE").unwrap();
+    spin(input, &program, 1_000_000_000).load
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tilt {
+    North,
+    West,
+    South,
+    East,
+}
+
+// A sequence of tilts, one letter per tilt, such as "NWSE".  On error,
+// returns the first character that isn't N, W, S or E.
+fn parse_program(program: &str) -> Result<Vec<Tilt>, char> {
+    program.chars().map(|c| match c.to_ascii_uppercase() {
+        'N' => Ok(Tilt::North),
+        'W' => Ok(Tilt::West),
+        'S' => Ok(Tilt::South),
+        'E' => Ok(Tilt::East),
+        _ => Err(c),
+    })
+    .collect()
+}
+
+// What happened when running a tilt program repeatedly.  The state after
+// `cycle_start + cycle_length` spins is the same as after `cycle_start`
+// spins.  If the target was reached before a repeat was seen, the cycle
+// is None.  `load` is the total load after the target number of spins.
+#[derive(Debug, PartialEq, Eq)]
+struct SpinReport {
+    cycle: Option<(usize, usize)>,
+    load: usize,
+}
+
+// Run `program` (one spin) `target` times, skipping ahead once the
+// positions of the rocks start repeating.
+fn spin(input: &str, program: &[Tilt], target: usize) -> SpinReport {
+    let mut puzzle = Puzzle::from_str(input);
+
+    // `history` maps each state to the number of spins that produced
+    // it; `loads[i]` is the load after `i` spins.
+    let mut history = HashMap::<Vec<u128>, usize>::new();
+    let mut loads = Vec::new();
+
+    for spins in 0.. {
+        if spins == target {
+            return SpinReport { cycle: None, load: puzzle.total_load() };
+        }
+        if let Some(&cycle_start) = history.get(&puzzle.state()) {
+            let cycle_length = spins - cycle_start;
+            let offset = (target - cycle_start) % cycle_length;
+            return SpinReport {
+                cycle: Some((cycle_start, cycle_length)),
+                load: loads[cycle_start + offset],
+            };
+        }
+        history.insert(puzzle.state(), spins);
+        loads.push(puzzle.total_load());
+
+        for &tilt in program {
+            puzzle.tilt(tilt);
+        }
+    }
+    unreachable!()
+}
+
+// The load after some number of spins, and the earlier spin (if any)
+// that left the rocks in the same positions.
+#[derive(Debug, PartialEq, Eq)]
+struct SpinRecord {
+    spin: usize,
+    load: usize,
+    repeat_of: Option<usize>,
+}
+
+// Run `program` `spins` times without skipping ahead, calling `on_tilt`
+// with the puzzle, the spin number (from 1) and the tilt after every
+// tilt.  Returns a record for the initial state and after each spin.
+fn spin_trace(
+    input: &str,
+    program: &[Tilt],
+    spins: usize,
+    mut on_tilt: impl FnMut(&Puzzle, usize, Tilt),
+) -> Vec<SpinRecord> {
     let mut puzzle = Puzzle::from_str(input);
+    let mut history = HashMap::<Vec<u128>, usize>::new();
+    let mut records = Vec::new();
 
-    // key is state, value is (iteration seen, load value)
-    let mut history = HashMap::<Puzzle, (usize, usize)>::new();
-
-    for i in 0..1_000_000_000 {
-        // Do the North, West, South, East tilts
-        puzzle.tilt_north();
-        puzzle.tilt_west();
-        puzzle.tilt_south();
-        puzzle.tilt_east();
-
-        // See if we've seen this state before
-        if let Some((prior, _load)) = history.get(&puzzle) {
-            let cycle_length = i - prior;
-            let remainder = (999_999_999 - i) % cycle_length;
-            // Return the key for value `prior + remainder`
-            return history.iter().find(|(_k,(i,_l))| i == &(prior + remainder)).unwrap().1.1;
-        } else {
-            history.insert(puzzle.clone(), (i, puzzle.total_load()));
+    for spin in 0..=spins {
+        if spin > 0 {
+            for &tilt in program {
+                puzzle.tilt(tilt);
+                on_tilt(&puzzle, spin, tilt);
+            }
         }
+        let repeat_of = history.get(&puzzle.state()).copied();
+        history.entry(puzzle.state()).or_insert(spin);
+        records.push(SpinRecord { spin, load: puzzle.total_load(), repeat_of });
     }
-    
-    panic!("No cycle found!");
+    records
 }
 
-#[derive(PartialEq, Eq, Hash, Clone)]
+// A CSV with one row per spin.  The phase is "transient" before the first
+// state that repeats, and "periodic" from then on; the spin at which the
+// repeat is noticed is marked "detected".
+fn to_csv(records: &[SpinRecord]) -> String {
+    let detected = records.iter().find(|record| record.repeat_of.is_some());
+    let mut result = String::from("spin,load,phase,repeat_of\n");
+    for record in records {
+        let phase = match detected {
+            Some(d) if d.spin == record.spin => "detected",
+            Some(d) if record.spin >= d.repeat_of.unwrap() => "periodic",
+            _ => "transient",
+        };
+        let repeat_of = record.repeat_of.map_or(String::new(), |spin| spin.to_string());
+        result.push_str(&format!("{},{},{phase},{repeat_of}\n", record.spin, record.load));
+    }
+    result
+}
+
+// The largest grid a bitboard can hold, in either direction.
+const MAX_SIZE: usize = 128;
+
+// One u128 per line; bit `i` of line `j` is the cell at index `i` along
+// line `j`.  Outside the grid, every bit is zero.
+type Bitboard = [u128; MAX_SIZE];
+
+// A run of cells along a line between two cube rocks (or the edges of
+// the grid).  Round rocks can only move within a segment.
+#[derive(Clone, Copy)]
+struct Segment {
+    start: u32,
+    end: u32,
+    mask: u128,
+}
+
+//
+// The round rocks are a bitboard of rows.  Tilting east or west moves
+// rocks along the rows: for each segment, count the rocks in it and
+// pack that many bits against one end.  Tilting north or south does
+// the same along the columns, by transposing the bitboard first.
+// Cube rocks never move, so their segments are found once.
+//
+#[derive(Clone)]
 struct Puzzle {
-    grid: Vec<Vec<char>>,
+    round: Bitboard,
+    cubes: Bitboard,
+    row_segments: Vec<Vec<Segment>>,
+    col_segments: Vec<Vec<Segment>>,
     num_rows: usize,
     num_cols: usize,
 }
 
-
 impl Puzzle {
     fn from_str(input: &str) -> Puzzle {
-        let grid: Vec<Vec<char>> = input.lines().map(|line| line.chars().collect()).collect();
+        let grid: Vec<&[u8]> = input.lines().map(str::as_bytes).collect();
         let num_rows = grid.len();
         let num_cols = grid[0].len();
-        
-        Puzzle { grid, num_rows, num_cols }
-    }
+        assert!(num_rows <= MAX_SIZE && num_cols <= MAX_SIZE, "grid too large");
 
-    fn tilt_north(&mut self) {
-        for row in 1..self.num_rows {
-            for col in 0..self.num_cols {
-                // If this row/column has an 'O', try to shift it up as
-                // much as possible.
-                if self.grid[row][col] == 'O' {
-                    let mut r = row;
-                    while r > 0 && self.grid[r-1][col] == '.' {
-                        r -= 1;
-                    }
-                    if r != row {
-                        self.grid[row][col] = '.';
-                        self.grid[r][col] = 'O';
-                    }
+        let mut round = [0; MAX_SIZE];
+        let mut cubes = [0; MAX_SIZE];
+        for (row, line) in grid.iter().enumerate() {
+            assert_eq!(line.len(), num_cols, "ragged grid");
+            for (col, &b) in line.iter().enumerate() {
+                match b {
+                    b'O' => round[row] |= 1 << col,
+                    b'#' => cubes[row] |= 1 << col,
+                    b'.' => {}
+                    _ => panic!("invalid character {:?}", b as char),
                 }
             }
         }
+
+        let row_segments = segments(&cubes, num_rows, num_cols);
+        let mut transposed = cubes;
+        transpose(&mut transposed);
+        let col_segments = segments(&transposed, num_cols, num_rows);
+
+        Puzzle { round, cubes, row_segments, col_segments, num_rows, num_cols }
     }
 
-    fn tilt_south(&mut self) {
-        for row in (0..self.num_rows-1).rev() {
-            for col in 0..self.num_cols {
-                // If this row/column has an 'O', try to shift it up as
-                // much as possible.
-                if self.grid[row][col] == 'O' {
-                    let mut r = row;
-                    while r < self.num_rows-1 && self.grid[r+1][col] == '.' {
-                        r += 1;
-                    }
-                    if r != row {
-                        self.grid[row][col] = '.';
-                        self.grid[r][col] = 'O';
-                    }
-                }
-            }
+    // A compact copy of the positions of the round rocks, suitable as
+    // a key for detecting repeated states.
+    fn state(&self) -> Vec<u128> {
+        self.round[..self.num_rows].to_vec()
+    }
+
+    fn tilt(&mut self, tilt: Tilt) {
+        match tilt {
+            Tilt::North => self.tilt_north(),
+            Tilt::West => self.tilt_west(),
+            Tilt::South => self.tilt_south(),
+            Tilt::East => self.tilt_east(),
         }
     }
 
+    fn tilt_north(&mut self) {
+        transpose(&mut self.round);
+        slide(&mut self.round, &self.col_segments, true);
+        transpose(&mut self.round);
+    }
+
+    fn tilt_south(&mut self) {
+        transpose(&mut self.round);
+        slide(&mut self.round, &self.col_segments, false);
+        transpose(&mut self.round);
+    }
+
     fn tilt_west(&mut self) {
-        for col in 1..self.num_cols {
-            for row in 0..self.num_rows {
-                // If this row/column has an 'O', try to shift it up as
-                // much as possible.
-                if self.grid[row][col] == 'O' {
-                    let mut c = col;
-                    while c > 0 && self.grid[row][c-1] == '.' {
-                        c -= 1;
-                    }
-                    if c != col {
-                        self.grid[row][col] = '.';
-                        self.grid[row][c] = 'O';
-                    }
-                }
-            }
-        }
+        slide(&mut self.round, &self.row_segments, true);
     }
 
     fn tilt_east(&mut self) {
-        for col in (0..self.num_cols-1).rev() {
-            for row in 0..self.num_rows {
-                // If this row/column has an 'O', try to shift it up as
-                // much as possible.
-                if self.grid[row][col] == 'O' {
-                    let mut c = col;
-                    while c < self.num_cols-1 && self.grid[row][c+1] == '.' {
-                        c += 1;
-                    }
-                    if c != col {
-                        self.grid[row][col] = '.';
-                        self.grid[row][c] = 'O';
-                    }
-                }
-            }
-        }
+        slide(&mut self.round, &self.row_segments, false);
     }
 
     fn total_load(&self) -> usize {
-        self.grid.iter().enumerate().map(|(i,row)| {
+        self.round[..self.num_rows].iter().enumerate().map(|(i, row)| {
             let load_multiplier = self.num_rows - i;
-            row.iter().filter(|c| c==&&'O').count() * load_multiplier
+            row.count_ones() as usize * load_multiplier
         })
         .sum()
     }
 }
 
+impl std::fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in 0..self.num_rows {
+            for col in 0..self.num_cols {
+                let c = if self.round[row] & (1 << col) != 0 {
+                    'O'
+                } else if self.cubes[row] & (1 << col) != 0 {
+                    '#'
+                } else {
+                    '.'
+                };
+                write!(f, "{c}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// The segments between cube rocks along each of `num_lines` lines of
+// length `len`.  Empty segments are left out.
+fn segments(cubes: &Bitboard, num_lines: usize, len: usize) -> Vec<Vec<Segment>> {
+    cubes[..num_lines].iter().map(|&line| {
+        let mut result = Vec::new();
+        let mut start = 0;
+        for i in 0..=len {
+            if i == len || line & (1 << i) != 0 {
+                if i > start {
+                    let (start, end) = (start as u32, i as u32);
+                    result.push(Segment { start, end, mask: ones(end - start) << start });
+                }
+                start = i + 1;
+            }
+        }
+        result
+    })
+    .collect()
+}
+
+// Move every rock in `lines` as far as it will go within its segment,
+// toward bit 0 if `toward_zero`, otherwise toward the end of the line.
+fn slide(lines: &mut Bitboard, segments: &[Vec<Segment>], toward_zero: bool) {
+    for (line, segments) in lines.iter_mut().zip(segments) {
+        let mut result = 0;
+        for segment in segments {
+            let count = (*line & segment.mask).count_ones();
+            let start = if toward_zero { segment.start } else { segment.end - count };
+            result |= ones(count) << start;
+        }
+        *line = result;
+    }
+}
+
+// A mask of the lowest `n` bits.
+fn ones(n: u32) -> u128 {
+    if n == 128 { !0 } else { (1 << n) - 1 }
+}
+
+// Swap rows and columns in place: bit `j` of line `i` moves to bit `i`
+// of line `j`.  This swaps the off-diagonal 64x64 blocks, then the
+// 32x32 blocks within each of those, and so on down to single bits.
+fn transpose(lines: &mut Bitboard) {
+    let mut width = MAX_SIZE / 2;
+    let mut mask: u128 = ones(64);
+    while width != 0 {
+        for k in 0..MAX_SIZE {
+            if k & width == 0 {
+                let t = ((lines[k] >> width) ^ lines[k + width]) & mask;
+                lines[k + width] ^= t;
+                lines[k] ^= t << width;
+            }
+        }
+        width /= 2;
+        mask ^= mask << width;
+    }
+}
+
 #[cfg(test)]
 static EXAMPLE1: &str = "\
 O....#....
@@ -179,3 +398,86 @@ fn test_part1() {
 fn test_part2() {
     assert_eq!(part2(EXAMPLE1), 64);
 }
+
+#[test]
+fn test_one_spin() {
+    let mut puzzle = Puzzle::from_str(EXAMPLE1);
+    puzzle.tilt_north();
+    puzzle.tilt_west();
+    puzzle.tilt_south();
+    puzzle.tilt_east();
+    assert_eq!(puzzle.to_string(), "\
+.....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....
+");
+}
+
+#[test]
+fn test_transpose() {
+    let mut lines = [0; MAX_SIZE];
+    lines[0] = 0b110;
+    lines[3] = 1 << 127;
+    lines[127] = 1 << 5;
+    transpose(&mut lines);
+    let mut expected = [0; MAX_SIZE];
+    expected[1] = 1;
+    expected[2] = 1;
+    expected[127] = 1 << 3;
+    expected[5] = 1 << 127;
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn test_spin_report() {
+    let program = parse_program("NWSE").unwrap();
+    assert_eq!(spin(EXAMPLE1, &program, 1_000_000_000), SpinReport { cycle: Some((3, 7)), load: 64 });
+    assert_eq!(spin(EXAMPLE1, &program, 1), SpinReport { cycle: None, load: 87 });
+    assert_eq!(spin(EXAMPLE1, &program, 0), SpinReport { cycle: None, load: 104 });
+}
+
+#[test]
+fn test_other_programs() {
+    // Tilting north over and over is the same as tilting north once.
+    let north = parse_program("N").unwrap();
+    assert_eq!(spin(EXAMPLE1, &north, 12345), SpinReport { cycle: Some((1, 1)), load: 136 });
+
+    // The cycle found must match running the program the long way.
+    let program = parse_program("nnesw").unwrap();
+    let mut puzzle = Puzzle::from_str(EXAMPLE1);
+    for _ in 0..100 {
+        for &tilt in &program {
+            puzzle.tilt(tilt);
+        }
+    }
+    assert_eq!(spin(EXAMPLE1, &program, 100).load, puzzle.total_load());
+
+    assert_eq!(parse_program("NWX"), Err('X'));
+}
+
+#[test]
+fn test_spin_trace() {
+    let program = parse_program("NWSE").unwrap();
+    let mut tilts = 0;
+    let records = spin_trace(EXAMPLE1, &program, 11, |_, _, _| tilts += 1);
+    assert_eq!(tilts, 44);
+    let loads: Vec<usize> = records.iter().map(|record| record.load).collect();
+    assert_eq!(loads, vec![104, 87, 69, 69, 69, 65, 64, 65, 63, 68, 69, 69]);
+    assert_eq!(records[10].repeat_of, Some(3));
+    assert_eq!(records[11].repeat_of, Some(4));
+
+    let csv = to_csv(&records);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "spin,load,phase,repeat_of");
+    assert_eq!(lines[3], "2,69,transient,");
+    assert_eq!(lines[4], "3,69,periodic,");
+    assert_eq!(lines[11], "10,69,detected,3");
+    assert_eq!(lines[12], "11,69,periodic,4");
+}
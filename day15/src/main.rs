@@ -5,9 +5,22 @@ fn main() {
     println!("Part 1: {result1}");
     assert_eq!(result1, 517551);
 
-    let result2 = part2(input);
+    let result2 = part2(input).expect("valid initialization sequence");
     println!("Part 2: {result2}");
     assert_eq!(result2, 286097);
+
+    // With `--trace [STEPS]`, show each step and the box it changed,
+    // stopping after STEPS steps, or with `--until LABEL`, after the
+    // first step that touches LABEL.
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--trace") {
+        let max_steps = args.get(i + 1).and_then(|s| s.parse().ok());
+        let until = args.iter().position(|arg| arg == "--until").map(|i| {
+            args.get(i + 1).expect("--until needs a label").as_str()
+        });
+        println!();
+        print!("{}", trace(input, max_steps, until).expect("valid initialization sequence"));
+    }
 }
 
 fn hash_str(word: &str) -> u32 {
@@ -19,60 +32,203 @@ fn part1(input: &str) -> u32 {
     input.split(',').map(hash_str).sum()
 }
 
-fn part2(input: &str) -> u32 {
-    // Create our empty hash table
-    let mut hashmap: Vec<Vec<BucketItem>> = Vec::new();
-    for _ in 0..256 {
-        hashmap.push(Vec::new());
+fn part2(input: &str) -> Result<u32, StepError> {
+    let mut boxes = LensBoxes::new();
+    for word in input.split(',') {
+        boxes.apply(&Step::parse(word)?);
     }
+    Ok(boxes.focusing_power())
+}
 
-    // Insert our items into the hashmap
-    for word in input.split(',') {
-        if let Some((label, focal_length)) = word.split_once('=') {
-            let focal_length: u32 = focal_length.parse().unwrap();
-            let bucket_index = hash_str(label) as usize;
-            let mut found = false;
-            for item in hashmap[bucket_index].iter_mut() {
-                if item.label == label {
-                    item.focal_length = focal_length;
-                    found = true;
-                }
+//
+// Run the initialization sequence one step at a time, describing each
+// step and then the box it changed, as in the puzzle text:
+//
+//   After "cm=2" (box 0, replace focal length 1 with 2):
+//   Box 0: [rn 1] [cm 2]
+//
+// Stops after `max_steps` steps, or after the first step whose label
+// is `until`, whichever comes first.
+//
+fn trace(input: &str, max_steps: Option<usize>, until: Option<&str>) -> Result<String, StepError> {
+    let mut boxes = LensBoxes::new();
+    let mut result = String::new();
+    for (i, word) in input.split(',').enumerate() {
+        if max_steps == Some(i) {
+            break;
+        }
+        let step = Step::parse(word)?;
+        let label = step.label();
+        let box_num = hash_str(label) as usize;
+        let operation = match (&step, boxes.get(label)) {
+            (Step::Insert { focal_length, .. }, Some(old)) => {
+                format!("replace focal length {old} with {focal_length}")
             }
-            if !found {
-                hashmap[bucket_index].push(BucketItem {
-                    label,
-                    focal_length,
-                });
+            (Step::Insert { focal_length, .. }, None) => format!("insert focal length {focal_length}"),
+            (Step::Remove { .. }, Some(old)) => format!("remove focal length {old}"),
+            (Step::Remove { .. }, None) => "remove, but it isn't there".to_string(),
+        };
+        boxes.apply(&step);
+        result.push_str(&format!("After {word:?} (box {box_num}, {operation}):\n"));
+        result.push_str(&format!("{}\n\n", boxes.describe_box(box_num)));
+        if until == Some(label) {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+// One comma-separated step of the initialization sequence.
+#[derive(Debug, PartialEq, Eq)]
+enum Step<'a> {
+    Insert { label: &'a str, focal_length: u32 },
+    Remove { label: &'a str },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum StepError {
+    EmptyLabel(String),
+    NoOperation(String),
+    BadFocalLength(String),
+    TrailingText(String),
+}
+
+impl std::fmt::Display for StepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepError::EmptyLabel(step) => write!(f, "step {step:?} has no label"),
+            StepError::NoOperation(step) => write!(f, "step {step:?} has no '=' or '-'"),
+            StepError::BadFocalLength(step) => write!(f, "step {step:?} has an invalid focal length"),
+            StepError::TrailingText(step) => write!(f, "step {step:?} has text after '-'"),
+        }
+    }
+}
+
+impl std::error::Error for StepError {}
+
+impl<'a> Step<'a> {
+    fn parse(word: &'a str) -> Result<Self, StepError> {
+        let error = |make: fn(String) -> StepError| make(word.to_string());
+        let step = if let Some((label, focal_length)) = word.split_once('=') {
+            let focal_length = focal_length.parse().map_err(|_| error(StepError::BadFocalLength))?;
+            Step::Insert { label, focal_length }
+        } else if let Some((label, rest)) = word.split_once('-') {
+            if !rest.is_empty() {
+                return Err(error(StepError::TrailingText));
             }
-        } else if let Some((label, empty)) = word.split_once('-') {
-            assert!(empty.is_empty());
-            let bucket_index = hash_str(label) as usize;
-            hashmap[bucket_index].retain(|item| item.label != label);
+            Step::Remove { label }
         } else {
-            panic!("syntax error");
+            return Err(error(StepError::NoOperation));
+        };
+        if step.label().is_empty() {
+            return Err(error(StepError::EmptyLabel));
+        }
+        Ok(step)
+    }
+
+    fn label(&self) -> &'a str {
+        match *self {
+            Step::Insert { label, .. } | Step::Remove { label } => label,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Lens<'a> {
+    label: &'a str,
+    focal_length: u32,
+}
+
+//
+// The HASHMAP: 256 boxes, chosen by the HASH of a lens's label.  Within
+// a box, lenses stay in the order they were first inserted; replacing
+// a lens keeps its place.
+//
+struct LensBoxes<'a> {
+    boxes: Vec<Vec<Lens<'a>>>,
+}
+
+impl<'a> LensBoxes<'a> {
+    fn new() -> Self {
+        LensBoxes { boxes: vec![Vec::new(); 256] }
+    }
+
+    fn apply(&mut self, step: &Step<'a>) {
+        match *step {
+            Step::Insert { label, focal_length } => self.insert(label, focal_length),
+            Step::Remove { label } => {
+                self.remove(label);
+            }
+        }
+    }
+
+    // Put a lens in its box, replacing any lens with the same label.
+    fn insert(&mut self, label: &'a str, focal_length: u32) {
+        let bucket = &mut self.boxes[hash_str(label) as usize];
+        match bucket.iter_mut().find(|lens| lens.label == label) {
+            Some(lens) => lens.focal_length = focal_length,
+            None => bucket.push(Lens { label, focal_length }),
         }
     }
 
+    // Take out the lens with this label, returning its focal length.
+    fn remove(&mut self, label: &str) -> Option<u32> {
+        let bucket = &mut self.boxes[hash_str(label) as usize];
+        let index = bucket.iter().position(|lens| lens.label == label)?;
+        Some(bucket.remove(index).focal_length)
+    }
+
+    fn get(&self, label: &str) -> Option<u32> {
+        self.boxes[hash_str(label) as usize]
+            .iter()
+            .find(|lens| lens.label == label)
+            .map(|lens| lens.focal_length)
+    }
+
+    // A single box, such as "Box 0: [rn 1] [cm 2]" or "Box 3: empty"
+    fn describe_box(&self, box_num: usize) -> String {
+        let bucket = &self.boxes[box_num];
+        if bucket.is_empty() {
+            format!("Box {box_num}: empty")
+        } else {
+            format!("Box {box_num}: {}", bucket.iter().map(Lens::to_string).collect::<Vec<_>>().join(" "))
+        }
+    }
+
+    // Every lens with its box number and slot (both from 0), in order.
+    fn iter(&self) -> impl Iterator<Item = (usize, usize, &Lens<'a>)> {
+        self.boxes.iter().enumerate().flat_map(|(box_num, bucket)| {
+            bucket.iter().enumerate().map(move |(slot, lens)| (box_num, slot, lens))
+        })
+    }
+
     // Computing the result
     // for all (box, label, focal_length):
     //      (box+1) * index_of(label)_in_box * focal_length
     // .sum()
-    hashmap
-        .into_iter()
-        .enumerate()
-        .map(|(box_num, bucket)| {
-            bucket
-                .into_iter()
-                .enumerate()
-                .map(|(i, item)| (box_num as u32 + 1) * (i as u32 + 1) * item.focal_length)
-                .sum::<u32>()
-        })
-        .sum()
+    fn focusing_power(&self) -> u32 {
+        self.iter()
+            .map(|(box_num, slot, lens)| (box_num as u32 + 1) * (slot as u32 + 1) * lens.focal_length)
+            .sum()
+    }
 }
 
-struct BucketItem<'label> {
-    label: &'label str,
-    focal_length: u32,
+impl std::fmt::Display for Lens<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{} {}]", self.label, self.focal_length)
+    }
+}
+
+// The non-empty boxes, as in the puzzle: "Box 0: [rn 1] [cm 2]"
+impl std::fmt::Display for LensBoxes<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (box_num, bucket) in self.boxes.iter().enumerate() {
+            if !bucket.is_empty() {
+                writeln!(f, "{}", self.describe_box(box_num))?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -85,5 +241,68 @@ fn test_part1() {
 
 #[test]
 fn test_part2() {
-    assert_eq!(part2(EXAMPLE1), 145);
+    assert_eq!(part2(EXAMPLE1), Ok(145));
+}
+
+#[test]
+fn test_lens_boxes() {
+    let mut boxes = LensBoxes::new();
+    for word in EXAMPLE1.split(',') {
+        boxes.apply(&Step::parse(word).unwrap());
+    }
+    assert_eq!(boxes.to_string(), "\
+Box 0: [rn 1] [cm 2]
+Box 3: [ot 7] [ab 5] [pc 6]
+");
+    assert_eq!(boxes.get("ot"), Some(7));
+    assert_eq!(boxes.get("qp"), None);
+
+    // Replacing keeps the slot; removing closes the gap.
+    boxes.insert("ot", 3);
+    assert_eq!(boxes.remove("ab"), Some(5));
+    assert_eq!(boxes.remove("ab"), None);
+    let lenses: Vec<(usize, usize, &str, u32)> = boxes
+        .iter()
+        .map(|(box_num, slot, lens)| (box_num, slot, lens.label, lens.focal_length))
+        .collect();
+    assert_eq!(lenses, vec![(0, 0, "rn", 1), (0, 1, "cm", 2), (3, 0, "ot", 3), (3, 1, "pc", 6)]);
+    assert_eq!(boxes.focusing_power(), 1 + 2 * 2 + 4 * 3 + 4 * 2 * 6);
+}
+
+#[test]
+fn test_parse_step() {
+    assert_eq!(Step::parse("rn=1"), Ok(Step::Insert { label: "rn", focal_length: 1 }));
+    assert_eq!(Step::parse("cm-"), Ok(Step::Remove { label: "cm" }));
+    assert_eq!(Step::parse("rn"), Err(StepError::NoOperation("rn".to_string())));
+    assert_eq!(Step::parse("rn=x"), Err(StepError::BadFocalLength("rn=x".to_string())));
+    assert_eq!(Step::parse("cm-2"), Err(StepError::TrailingText("cm-2".to_string())));
+    assert_eq!(Step::parse("=4"), Err(StepError::EmptyLabel("=4".to_string())));
+    assert_eq!(part2("rn=1,oops"), Err(StepError::NoOperation("oops".to_string())));
+}
+
+#[test]
+fn test_trace() {
+    let expected = "\
+After \"rn=1\" (box 0, insert focal length 1):
+Box 0: [rn 1]
+
+After \"cm-\" (box 0, remove, but it isn't there):
+Box 0: [rn 1]
+
+After \"qp=3\" (box 1, insert focal length 3):
+Box 1: [qp 3]
+
+After \"cm=2\" (box 0, insert focal length 2):
+Box 0: [rn 1] [cm 2]
+
+After \"qp-\" (box 1, remove focal length 3):
+Box 1: empty
+
+";
+    assert_eq!(trace(EXAMPLE1, Some(5), None).as_deref(), Ok(expected));
+    // "qp" is first touched by the third step.
+    assert_eq!(trace(EXAMPLE1, None, Some("qp")).unwrap().matches("After").count(), 3);
+    assert_eq!(trace(EXAMPLE1, Some(4), Some("qp")).unwrap().matches("After").count(), 3);
+    assert_eq!(trace(EXAMPLE1, None, None).unwrap().matches("After").count(), 11);
+    assert!(trace(EXAMPLE1, None, None).unwrap().contains("(box 3, replace focal length 9 with 7)"));
 }
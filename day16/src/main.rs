@@ -10,44 +10,59 @@ fn main() {
     let result2 = part2(input);
     println!("Part 2: {result2}");
     assert_eq!(result2, 7572);
+
+    // `--render` draws the beams from the best entry point in the
+    // terminal; `--render-file FILE` saves them as plain text.
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--render") {
+        let entry = BeamGraph::new(input).best_entry_point().0;
+        print!("{}", render(input, entry, true));
+    }
+    if let Some(pos) = args.iter().position(|arg| arg == "--render-file") {
+        let path = args.get(pos + 1).expect("a file name after --render-file");
+        let entry = BeamGraph::new(input).best_entry_point().0;
+        std::fs::write(path, render(input, entry, false)).expect("write the picture");
+    }
 }
 
 fn part1(input: &str) -> usize {
-    let grid: Vec<Vec<char>> = input.lines().map(|line| line.chars().collect()).collect();
-    calculate_energized(&grid, 0, 0, Direction::Right)
+    let graph = BeamGraph::new(input);
+    graph.energized_from(0, 0, Direction::Right)
 }
 
+//
+// Every beam that hits a splitter side-on continues exactly the same
+// way, no matter where it came from.  So the work is shared: find where
+// each splitter's two beams go (the beam graph), and the tiles reachable
+// from each splitter.  Then each entry point only has to follow its
+// beam to the first splitter.
+//
 fn part2(input: &str) -> usize {
-    let grid: Vec<Vec<char>> = input.lines().map(|line| line.chars().collect()).collect();
-    let num_rows = grid.len() as isize;
-    let num_cols = grid[0].len() as isize;
-
-    let max_right = (0..num_rows)
-        .map(|start_row| calculate_energized(&grid, start_row, 0, Direction::Right))
-        .max()
-        .unwrap();
-    let max_left = (0..num_rows)
-        .map(|start_row| calculate_energized(&grid, start_row, num_cols-1, Direction::Left))
-        .max()
-        .unwrap();
-    let max_down = (0..num_cols)
-        .map(|start_col| calculate_energized(&grid, 0, start_col, Direction::Down))
-        .max()
-        .unwrap();
-    let max_up = (0..num_cols)
-        .map(|start_col| calculate_energized(&grid, num_rows-1, start_col, Direction::Up))
-        .max()
-        .unwrap();
-
-    [max_right, max_left, max_down, max_up].into_iter().max().unwrap()
+    BeamGraph::new(input).best_entry_point().1
 }
 
+#[cfg(test)]
 fn calculate_energized(
     grid: &[Vec<char>],
     start_row: isize,
     start_col: isize,
     direction: Direction,
 ) -> usize {
+    // Count the number of unique locations (ignoring incoming direction)
+    beams(grid, start_row, start_col, direction)
+        .into_iter()
+        .map(|(row, col, _dir)| (row, col))
+        .collect::<HashSet<(isize, isize)>>()
+        .len()
+}
+
+// Every (row, column, direction) that a beam passes through.
+fn beams(
+    grid: &[Vec<char>],
+    start_row: isize,
+    start_col: isize,
+    direction: Direction,
+) -> HashSet<(isize, isize, Direction)> {
     let num_rows = grid.len() as isize;
     let num_cols = grid[0].len() as isize;
     let mut energized: HashSet<(isize, isize, Direction)> = HashSet::new();
@@ -91,12 +106,376 @@ fn calculate_energized(
         }
     }
 
-    // Count the number of unique locations (ignoring incoming direction)
     energized
-        .into_iter()
-        .map(|(row, col, _dir)| (row, col))
-        .collect::<HashSet<(isize, isize)>>()
-        .len()
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_ENERGIZED: &str = "\x1b[0;1;33m";    // bold yellow
+const ANSI_DARK: &str = "\x1b[0;2;37m";         // dim grey
+const ANSI_ENTRY: &str = "\x1b[0;1;31m";        // bold red
+
+//
+// Draw the contraption with the beams from `entry`.  Mirrors and
+// splitters are drawn as themselves; empty tiles crossed by a beam show
+// its direction as an arrow (↕ or ↔ for beams both ways along a line,
+// or the number of beams, like the puzzle text).  A border of spaces
+// goes round the grid, with an arrow marking where the beam comes in.
+//
+// With `ansi`, energized tiles are coloured.  Otherwise, a second copy
+// of the grid follows, with energized tiles drawn as '#'.
+//
+fn render(input: &str, entry: (usize, usize, Direction), ansi: bool) -> String {
+    let grid: Vec<Vec<char>> = input.lines().map(|line| line.chars().collect()).collect();
+    let num_rows = grid.len();
+    let num_cols = grid[0].len();
+    let (entry_row, entry_col, entry_dir) = entry;
+
+    let mut directions = vec![vec![Vec::new(); num_cols]; num_rows];
+    for (row, col, dir) in beams(&grid, entry_row as isize, entry_col as isize, entry_dir) {
+        directions[row as usize][col as usize].push(dir);
+    }
+
+    // The margin cell next to the entry point, in bordered coordinates.
+    let marker = match entry_dir {
+        Direction::Right => (entry_row + 1, 0),
+        Direction::Left => (entry_row + 1, num_cols + 1),
+        Direction::Down => (0, entry_col + 1),
+        Direction::Up => (num_rows + 1, entry_col + 1),
+    };
+
+    let mut result = String::new();
+    for row in 0..num_rows + 2 {
+        for col in 0..num_cols + 2 {
+            let (color, glyph) = if (row, col) == marker {
+                (ANSI_ENTRY, arrow(entry_dir))
+            } else if row == 0 || col == 0 || row > num_rows || col > num_cols {
+                (ANSI_DARK, ' ')
+            } else {
+                let tile = grid[row - 1][col - 1];
+                let dirs = &mut directions[row - 1][col - 1];
+                dirs.sort_unstable_by_key(|&dir| dir as u8);
+                let glyph = match (tile, dirs.as_slice()) {
+                    ('.', []) => '.',
+                    ('.', [dir]) => arrow(*dir),
+                    ('.', [Direction::Up, Direction::Down]) => '↕',
+                    ('.', [Direction::Right, Direction::Left]) => '↔',
+                    ('.', dirs) => char::from_digit(dirs.len() as u32, 10).unwrap(),
+                    (tile, _) => tile,
+                };
+                (if dirs.is_empty() { ANSI_DARK } else { ANSI_ENERGIZED }, glyph)
+            };
+            if ansi {
+                result.push_str(color);
+            }
+            result.push(glyph);
+        }
+        if ansi {
+            result.push_str(ANSI_RESET);
+        }
+        result.push('\n');
+    }
+
+    if !ansi {
+        result.push('\n');
+        for row in &directions {
+            result.extend(row.iter().map(|dirs| if dirs.is_empty() { '.' } else { '#' }));
+            result.push('\n');
+        }
+    }
+    result
+}
+
+fn arrow(dir: Direction) -> char {
+    match dir {
+        Direction::Up => '↑',
+        Direction::Down => '↓',
+        Direction::Right => '→',
+        Direction::Left => '←',
+    }
+}
+
+// The path of a beam from where it starts (or leaves a splitter) up to
+// the next splitter it hits side-on.  `cells` are the tiles it passes
+// through, as `row * num_cols + col`, not including that splitter.
+// `target` is that splitter's node number, or None if the beam leaves
+// the grid (or goes round in a loop).
+#[derive(Debug)]
+struct Segment {
+    cells: Vec<usize>,
+    target: Option<usize>,
+}
+
+//
+// The nodes of the beam graph are the splitters, and each has two edges:
+// the segments leading away from it.  Beams can go round in circles, so
+// the graph is condensed into its strongly connected components.  Every
+// splitter in a component energizes the same tiles, so those are worked
+// out once per component, reusing the sets of the components it leads to.
+//
+struct BeamGraph {
+    grid: Vec<Vec<char>>,
+    num_rows: usize,
+    num_cols: usize,
+    // the node number of the splitter at each cell, if any
+    node_at: Vec<Option<usize>>,
+    // the component of each node
+    component: Vec<usize>,
+    // the tiles energized by a beam hitting any splitter in a component
+    energized: Vec<Bitset>,
+}
+
+impl BeamGraph {
+    fn new(input: &str) -> Self {
+        let grid: Vec<Vec<char>> = input.lines().map(|line| line.chars().collect()).collect();
+        let num_rows = grid.len();
+        let num_cols = grid[0].len();
+
+        let mut splitters = Vec::new();
+        let mut node_at = vec![None; num_rows * num_cols];
+        for (row, line) in grid.iter().enumerate() {
+            for (col, &ch) in line.iter().enumerate() {
+                if ch == '|' || ch == '-' {
+                    node_at[row * num_cols + col] = Some(splitters.len());
+                    splitters.push((row, col));
+                }
+            }
+        }
+
+        let mut graph = BeamGraph {
+            grid,
+            num_rows,
+            num_cols,
+            node_at,
+            component: Vec::new(),
+            energized: Vec::new(),
+        };
+
+        let edges: Vec<[Segment; 2]> = splitters
+            .iter()
+            .map(|&(row, col)| {
+                let [a, b] = match graph.grid[row][col] {
+                    '|' => [Direction::Up, Direction::Down],
+                    _ => [Direction::Left, Direction::Right],
+                };
+                [graph.trace_from_splitter(row, col, a), graph.trace_from_splitter(row, col, b)]
+            })
+            .collect();
+
+        // Components come out of Tarjan's algorithm after every component
+        // they lead to, so their energized sets can be built in order.
+        let components = strongly_connected_components(&edges);
+        graph.component = vec![0; splitters.len()];
+        for (index, members) in components.iter().enumerate() {
+            for &node in members {
+                graph.component[node] = index;
+            }
+        }
+        for (index, members) in components.iter().enumerate() {
+            let mut tiles = Bitset::new(num_rows * num_cols);
+            for &node in members {
+                let (row, col) = splitters[node];
+                tiles.insert(row * num_cols + col);
+                for segment in &edges[node] {
+                    tiles.extend(&segment.cells);
+                    if let Some(target) = segment.target {
+                        if graph.component[target] != index {
+                            tiles.union_with(&graph.energized[graph.component[target]]);
+                        }
+                    }
+                }
+            }
+            graph.energized.push(tiles);
+        }
+
+        graph
+    }
+
+    // The entry point that energizes the most tiles, and how many.
+    fn best_entry_point(&self) -> ((usize, usize, Direction), usize) {
+        self.entry_points()
+            .map(|(row, col, dir)| ((row, col, dir), self.energized_from(row, col, dir)))
+            .max_by_key(|&(_, count)| count)
+            .unwrap()
+    }
+
+    // Every place a beam can enter from outside the grid.
+    fn entry_points(&self) -> impl Iterator<Item = (usize, usize, Direction)> + '_ {
+        let (last_row, last_col) = (self.num_rows - 1, self.num_cols - 1);
+        (0..self.num_rows)
+            .flat_map(move |row| [(row, 0, Direction::Right), (row, last_col, Direction::Left)])
+            .chain((0..self.num_cols).flat_map(move |col| {
+                [(0, col, Direction::Down), (last_row, col, Direction::Up)]
+            }))
+    }
+
+    // The number of tiles energized by a beam that starts at (row, col),
+    // heading in `dir`.
+    fn energized_from(&self, row: usize, col: usize, dir: Direction) -> usize {
+        let segment = self.trace(row, col, dir, None);
+        let mut tiles = match segment.target {
+            Some(node) => self.energized[self.component[node]].clone(),
+            None => Bitset::new(self.num_rows * self.num_cols),
+        };
+        tiles.extend(&segment.cells);
+        tiles.count()
+    }
+
+    fn trace_from_splitter(&self, row: usize, col: usize, dir: Direction) -> Segment {
+        let origin = row * self.num_cols + col;
+        match self.step(row, col, dir) {
+            Some((row, col)) => self.trace(row, col, dir, Some(origin)),
+            None => Segment { cells: Vec::new(), target: None },
+        }
+    }
+
+    // Follow a beam from (row, col) until it hits a splitter side-on or
+    // leaves the grid.  A beam leaving a splitter can pass back through
+    // it lengthwise and go round forever; stop if it gets back to the
+    // `origin` cell.  (A beam from outside the grid can't get into a
+    // loop, since mirrors are reversible.)
+    fn trace(&self, mut row: usize, mut col: usize, mut dir: Direction, origin: Option<usize>) -> Segment {
+        let mut cells = Vec::new();
+        loop {
+            let cell = row * self.num_cols + col;
+            let ch = self.grid[row][col];
+            let side_on = matches!(
+                (ch, dir),
+                ('|', Direction::Left | Direction::Right) | ('-', Direction::Up | Direction::Down)
+            );
+            if side_on {
+                return Segment { cells, target: self.node_at[cell] };
+            }
+            if origin == Some(cell) {
+                return Segment { cells, target: None };
+            }
+            cells.push(cell);
+            dir = match (ch, dir) {
+                ('/', Direction::Right) => Direction::Up,
+                ('/', Direction::Left) => Direction::Down,
+                ('/', Direction::Down) => Direction::Left,
+                ('/', Direction::Up) => Direction::Right,
+                ('\\', Direction::Right) => Direction::Down,
+                ('\\', Direction::Left) => Direction::Up,
+                ('\\', Direction::Down) => Direction::Right,
+                ('\\', Direction::Up) => Direction::Left,
+                _ => dir,
+            };
+            match self.step(row, col, dir) {
+                Some((r, c)) => (row, col) = (r, c),
+                None => return Segment { cells, target: None },
+            }
+        }
+    }
+
+    // The next cell in direction `dir`, if it is inside the grid.
+    fn step(&self, row: usize, col: usize, dir: Direction) -> Option<(usize, usize)> {
+        match dir {
+            Direction::Up => Some((row.checked_sub(1)?, col)),
+            Direction::Down => (row + 1 < self.num_rows).then_some((row + 1, col)),
+            Direction::Left => Some((row, col.checked_sub(1)?)),
+            Direction::Right => (col + 1 < self.num_cols).then_some((row, col + 1)),
+        }
+    }
+}
+
+// Tarjan's algorithm, on nodes numbered from 0, given each node's two
+// outgoing segments.  Components are listed in reverse topological order:
+// each one comes after every component reachable from it.
+fn strongly_connected_components(edges: &[[Segment; 2]]) -> Vec<Vec<usize>> {
+    struct Tarjan<'e> {
+        edges: &'e [[Segment; 2]],
+        index: Vec<Option<usize>>,
+        low_link: Vec<usize>,
+        next_index: usize,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        components: Vec<Vec<usize>>,
+    }
+
+    impl Tarjan<'_> {
+        fn visit(&mut self, node: usize) {
+            let index = self.next_index;
+            self.next_index += 1;
+            self.index[node] = Some(index);
+            self.low_link[node] = index;
+            self.stack.push(node);
+            self.on_stack[node] = true;
+
+            for child in self.edges[node].iter().filter_map(|segment| segment.target) {
+                match self.index[child] {
+                    None => {
+                        self.visit(child);
+                        self.low_link[node] = self.low_link[node].min(self.low_link[child]);
+                    }
+                    Some(child_index) if self.on_stack[child] => {
+                        self.low_link[node] = self.low_link[node].min(child_index);
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            if self.low_link[node] == index {
+                let mut component = Vec::new();
+                loop {
+                    let member = self.stack.pop().unwrap();
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        edges,
+        index: vec![None; edges.len()],
+        low_link: vec![0; edges.len()],
+        next_index: 0,
+        stack: Vec::new(),
+        on_stack: vec![false; edges.len()],
+        components: Vec::new(),
+    };
+    for node in 0..edges.len() {
+        if tarjan.index[node].is_none() {
+            tarjan.visit(node);
+        }
+    }
+    tarjan.components
+}
+
+// A fixed-size set of cell numbers, 64 to a word.
+#[derive(Debug, Clone)]
+struct Bitset {
+    words: Vec<u64>,
+}
+
+impl Bitset {
+    fn new(size: usize) -> Self {
+        Bitset { words: vec![0; size.div_ceil(64)] }
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    fn extend(&mut self, cells: &[usize]) {
+        for &cell in cells {
+            self.insert(cell);
+        }
+    }
+
+    fn union_with(&mut self, other: &Bitset) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -119,3 +498,77 @@ fn test_part1() {
 fn test_part2() {
     assert_eq!(part2(EXAMPLE1), 51);
 }
+
+#[test]
+fn test_every_entry_point() {
+    // The beam graph must agree with following every beam, from every
+    // entry point, including one that starts on a splitter and a loop
+    // that passes back through the splitter it started from.
+    let looped = "\
+./...\\
+.|....
+.\\-../
+......
+";
+    for input in [EXAMPLE1, looped, "|.-\n...\n-.|\n"] {
+        let grid: Vec<Vec<char>> = input.lines().map(|line| line.chars().collect()).collect();
+        let graph = BeamGraph::new(input);
+        for (row, col, dir) in graph.entry_points() {
+            assert_eq!(
+                graph.energized_from(row, col, dir),
+                calculate_energized(&grid, row as isize, col as isize, dir),
+                "entering at ({row}, {col}) heading {dir:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_render() {
+    // Compare the arrows with the puzzle text; the border is blank but
+    // for the entry arrow, which is in the left margin of the top row.
+    let rendered = render(EXAMPLE1, (0, 0, Direction::Right), false);
+    assert_eq!(rendered.lines().map(str::trim_end).collect::<Vec<_>>(), vec![
+        "",
+        "→→|←←←\\....",
+        " |↓-.\\↑....",
+        " .↓...|-→→→",
+        " .↓...↓↑.|.",
+        " .↓...↓↑...",
+        " .↓...↓↑..\\",
+        " .↓../2\\\\..",
+        " ←-→-/↓↓|..",
+        " .|←←←2-|.\\",
+        " .↓//.|.↓..",
+        "",
+        "",
+        "######....",
+        ".#...#....",
+        ".#...#####",
+        ".#...##...",
+        ".#...##...",
+        ".#...##...",
+        ".#..####..",
+        "########..",
+        ".#######..",
+        ".#...#.#..",
+    ]);
+
+    // Beams both ways along a row or column, and three ways at once.
+    let rendered = render("..\\\n-|.\n|-/\n", (0, 0, Direction::Right), false);
+    assert_eq!(rendered.lines().take(5).collect::<Vec<_>>(), vec![
+        "     ",
+        "→↔3\\ ",
+        " -|↕ ",
+        " |-/ ",
+        "     ",
+    ]);
+
+    // The best entry point for part 2 is marked above column 3.
+    let graph = BeamGraph::new(EXAMPLE1);
+    assert_eq!(graph.best_entry_point(), ((0, 3, Direction::Down), 51));
+    let rendered = render(EXAMPLE1, (0, 3, Direction::Down), true);
+    assert!(rendered.starts_with(&format!("{ANSI_DARK} {ANSI_DARK} {ANSI_DARK} {ANSI_DARK} {ANSI_ENTRY}↓")));
+    assert_eq!(rendered.matches(ANSI_ENERGIZED).count(), 51);
+    assert_eq!(rendered.matches(ANSI_RESET).count(), 12);
+}